@@ -0,0 +1,204 @@
+use std::collections::HashMap;
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use sarex::{ci::Ci, conn};
+
+/// Creates connector instances among `components` components. Sources are identified by host and
+/// port, and targets only by host, so that every target has to be resolved as a subset of a source.
+fn create_cis(len: usize, components: usize) -> Vec<Ci> {
+    (0..len)
+        .map(|i| {
+            let source = i % components;
+            let target = (i * 7 + 1) % components;
+
+            Ci {
+                id: format!("rule_call_{}", i),
+                procedure: String::from("call"),
+                index: i as u64,
+                connector_type: String::from(if i % 2 == 0 { "HTTP" } else { "gRPC" }),
+                source_component_values: HashMap::from([
+                    (String::from("host"), format!("host-{}", source)),
+                    (String::from("port"), format!("{}", 8000 + source)),
+                ]),
+                source_context_values: HashMap::from([(
+                    String::from("pid"),
+                    format!("{}", i % 16),
+                )]),
+                target_component_values: HashMap::from([(
+                    String::from("host"),
+                    format!("host-{}", target),
+                )]),
+                target_context_values: HashMap::new(),
+                location: None,
+            }
+        })
+        .collect()
+}
+
+/// The model construction of the first version of sarex, which scanned every component for each
+/// component of each connector instance (`find_component_in_model`), and every connector for each
+/// connector instance (`Model::has_connector`). Random component IDs are replaced by sequential
+/// ones, which does not change the cost of the scans.
+mod baseline {
+    use std::collections::HashMap;
+
+    use sarex::ci::Ci;
+
+    pub struct Model {
+        pub connectors: Vec<Connector>,
+        pub components: Vec<Component>,
+    }
+
+    impl Model {
+        fn has_connector(&self, connector: &Connector) -> bool {
+            for c in &self.connectors {
+                if c.connector_type == connector.connector_type
+                    && c.source_component_id == connector.source_component_id
+                    && c.target_component_id == connector.target_component_id
+                {
+                    return true;
+                }
+            }
+
+            false
+        }
+    }
+
+    pub struct Connector {
+        pub connector_type: String,
+        pub source_component_id: String,
+        pub target_component_id: String,
+    }
+
+    pub struct Component {
+        pub id: String,
+        pub component_values: HashMap<String, String>,
+    }
+
+    pub fn build_model(cis: Vec<Ci>) -> Model {
+        let mut model = Model {
+            connectors: Vec::new(),
+            components: Vec::new(),
+        };
+
+        for ci in &cis {
+            // Create source components first.
+            find_or_create_component_if_not_exist(&mut model, ci, true);
+        }
+
+        for ci in cis {
+            let source_component_id = find_or_create_component_if_not_exist(&mut model, &ci, true);
+            let target_component_id = find_or_create_component_if_not_exist(&mut model, &ci, false);
+
+            let new_connector = Connector {
+                connector_type: ci.connector_type,
+                source_component_id,
+                target_component_id,
+            };
+            if !model.has_connector(&new_connector) {
+                model.connectors.push(new_connector);
+            }
+        }
+
+        model
+    }
+
+    fn find_or_create_component_if_not_exist(
+        model: &mut Model,
+        ci: &Ci,
+        is_source: bool,
+    ) -> String {
+        let component_values = if is_source {
+            &ci.source_component_values
+        } else {
+            &ci.target_component_values
+        };
+
+        if let Some(component_id) = find_component_in_model(model, component_values) {
+            return component_id;
+        }
+
+        let new_component_id = format!("{}", model.components.len());
+        model.components.push(Component {
+            id: new_component_id.clone(),
+            component_values: component_values.clone(),
+        });
+
+        new_component_id
+    }
+
+    fn find_component_in_model(
+        model: &mut Model,
+        component_values: &HashMap<String, String>,
+    ) -> Option<String> {
+        for component in &mut model.components {
+            let is_component_large = component.component_values.len() > component_values.len();
+            let (large_values, small_values) = if is_component_large {
+                (&component.component_values, component_values)
+            } else {
+                (component_values, &component.component_values)
+            };
+
+            let mut is_same = true;
+            for (identifier, value) in small_values {
+                if !value.is_empty() {
+                    is_same = is_same && large_values.get(identifier) == Some(value);
+                }
+            }
+
+            if is_same {
+                if !is_component_large {
+                    for (identifier, value) in component_values {
+                        if !value.is_empty() && !component.component_values.contains_key(identifier)
+                        {
+                            component
+                                .component_values
+                                .insert(identifier.clone(), value.clone());
+                        }
+                    }
+                }
+
+                return Some(component.id.clone());
+            }
+        }
+
+        None
+    }
+}
+
+/// Measures `build_model` against the baseline with 100 connector instances per component, so
+/// that the number of components grows with the number of connector instances. The baseline
+/// grows quadratically, so it is only measured up to 100,000 connector instances, and only
+/// `build_model` is measured in the millions.
+fn bench_build_model(c: &mut Criterion) {
+    let mut group = c.benchmark_group("build_model");
+    group.sample_size(10);
+
+    for len in [10_000, 100_000, 1_000_000, 5_000_000] {
+        let components = len / 100;
+
+        group.throughput(Throughput::Elements(len as u64));
+        group.bench_with_input(BenchmarkId::new("indexed", len), &len, |b, &len| {
+            b.iter_batched(
+                || create_cis(len, components),
+                |cis| black_box(conn::build_model(cis.into_iter().map(Ok), false).unwrap()),
+                criterion::BatchSize::LargeInput,
+            )
+        });
+
+        if len <= 100_000 {
+            group.bench_with_input(BenchmarkId::new("baseline", len), &len, |b, &len| {
+                b.iter_batched(
+                    || create_cis(len, components),
+                    |cis| black_box(baseline::build_model(cis)),
+                    criterion::BatchSize::LargeInput,
+                )
+            });
+        }
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_build_model);
+criterion_main!(benches);
@@ -0,0 +1,393 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    error::Error,
+    fmt::{self, Display, Formatter},
+};
+
+use glob::Pattern;
+use serde::Deserialize;
+
+use crate::{
+    conn::{Component, Connector, Model},
+    versioned_file::{self, VersionedFile},
+};
+
+#[derive(Debug)]
+pub enum CheckError {
+    UnsupportedVersion(u32),
+    InvalidPattern(String),
+    UnknownComponent(String),
+}
+
+impl Error for CheckError {}
+
+impl Display for CheckError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            CheckError::UnsupportedVersion(v) => write!(
+                f,
+                "Unsupported architecture rule file version: {} (expected {})",
+                v,
+                ArchitectureRuleFile::VERSION
+            ),
+            CheckError::InvalidPattern(p) => write!(f, "Invalid pattern: {}", p),
+            CheckError::UnknownComponent(id) => {
+                write!(f, "A connector refers to an unknown component: {}", id)
+            }
+        }
+    }
+}
+
+/// What to do with connectors that match neither an allow rule nor a deny rule
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Policy {
+    #[default]
+    Allow,
+    Deny,
+}
+
+/// An intended architecture as allow and deny rules over connectors, e.g.,
+///
+/// ```yaml
+/// version: 1
+/// default: allow
+/// deny:
+///   - name: frontend must not talk to the database directly
+///     from: { service: "frontend*" }
+///     to: { db: "*" }
+/// ```
+///
+/// Unknown fields are rejected, so that a misspelled rule cannot silently allow everything.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ArchitectureRuleFile {
+    /// Checked by `read_versioned` before the rest of the file is read
+    #[serde(rename = "version")]
+    _version: u32,
+
+    #[serde(default)]
+    default: Policy,
+
+    #[serde(default)]
+    allow: Vec<ConnectorRuleEntry>,
+
+    #[serde(default)]
+    deny: Vec<ConnectorRuleEntry>,
+}
+
+impl VersionedFile for ArchitectureRuleFile {
+    const VERSION: u32 = 1;
+
+    fn unsupported_version(version: u32) -> Box<dyn Error> {
+        Box::new(CheckError::UnsupportedVersion(version))
+    }
+}
+
+/// A rule that selects connectors. Omitted fields select every connector.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConnectorRuleEntry {
+    #[serde(default)]
+    name: Option<String>,
+
+    /// Glob patterns of identifier values that the source component must have
+    #[serde(default)]
+    from: BTreeMap<String, String>,
+
+    /// Glob patterns of identifier values that the target component must have
+    #[serde(default)]
+    to: BTreeMap<String, String>,
+
+    /// A glob pattern of the connector type
+    #[serde(rename = "connectorType", default)]
+    connector_type: Option<String>,
+}
+
+pub struct ConnectorRule {
+    pub name: String,
+    from: Vec<(String, Pattern)>,
+    to: Vec<(String, Pattern)>,
+    connector_type: Option<Pattern>,
+}
+
+impl ConnectorRule {
+    fn matches(&self, connector: &Connector, source: &Component, target: &Component) -> bool {
+        self.connector_type
+            .as_ref()
+            .is_none_or(|pattern| pattern.matches(&connector.connector_type))
+            && matches_component(&self.from, source)
+            && matches_component(&self.to, target)
+    }
+}
+
+/// A component matches if it has every identifier of the selector with a matching value.
+fn matches_component(selector: &[(String, Pattern)], component: &Component) -> bool {
+    selector.iter().all(|(identifier, pattern)| {
+        component
+            .component_values
+            .get(identifier)
+            .is_some_and(|value| pattern.matches(value))
+    })
+}
+
+/// Allow and deny rules of an intended architecture. A connector violates the architecture if it
+/// matches a deny rule and no allow rule, i.e., allow rules are exceptions to deny rules. If the
+/// default policy is deny, every connector must also match an allow rule.
+pub struct ArchitectureRules {
+    pub default: Policy,
+    pub allow: Vec<ConnectorRule>,
+    pub deny: Vec<ConnectorRule>,
+}
+
+/// A connector that violates a rule
+pub struct Violation<'a> {
+    pub rule: String,
+    pub connector: &'a Connector,
+    pub source: &'a Component,
+    pub target: &'a Component,
+}
+
+/// Reads architecture rules from a YAML or JSON file. The format is chosen by the extension.
+pub fn read_rules(file_path_str: &str) -> Result<ArchitectureRules, Box<dyn Error>> {
+    let rule_file: ArchitectureRuleFile = versioned_file::read_versioned(file_path_str)?;
+
+    Ok(ArchitectureRules {
+        default: rule_file.default,
+        allow: compile_rules(rule_file.allow, "allow")?,
+        deny: compile_rules(rule_file.deny, "deny")?,
+    })
+}
+
+fn compile_rules(
+    entries: Vec<ConnectorRuleEntry>,
+    kind: &str,
+) -> Result<Vec<ConnectorRule>, CheckError> {
+    entries
+        .into_iter()
+        .enumerate()
+        .map(|(index, entry)| {
+            Ok(ConnectorRule {
+                name: entry
+                    .name
+                    .unwrap_or_else(|| format!("{} rule #{}", kind, index + 1)),
+                from: compile_selector(entry.from)?,
+                to: compile_selector(entry.to)?,
+                connector_type: entry
+                    .connector_type
+                    .as_deref()
+                    .map(compile_pattern)
+                    .transpose()?,
+            })
+        })
+        .collect()
+}
+
+fn compile_selector(
+    selector: BTreeMap<String, String>,
+) -> Result<Vec<(String, Pattern)>, CheckError> {
+    selector
+        .into_iter()
+        .map(|(identifier, pattern)| Ok((identifier, compile_pattern(&pattern)?)))
+        .collect()
+}
+
+fn compile_pattern(pattern: &str) -> Result<Pattern, CheckError> {
+    Pattern::new(pattern).map_err(|_| CheckError::InvalidPattern(pattern.to_string()))
+}
+
+/// Checks every connector of a model against architecture rules. Connectors that refer to unknown
+/// components are rejected, since they could not be checked.
+pub fn check_model<'a>(
+    model: &'a Model,
+    rules: &ArchitectureRules,
+) -> Result<Vec<Violation<'a>>, CheckError> {
+    let components: HashMap<&str, &Component> = model
+        .components
+        .iter()
+        .map(|component| (component.id.as_str(), component))
+        .collect();
+
+    let mut violations: Vec<Violation> = Vec::new();
+    for connector in &model.connectors {
+        let component = |id: &str| {
+            components
+                .get(id)
+                .ok_or_else(|| CheckError::UnknownComponent(id.to_string()))
+        };
+        let source = component(&connector.source_component_id)?;
+        let target = component(&connector.target_component_id)?;
+
+        let matches = |rule: &&ConnectorRule| rule.matches(connector, source, target);
+        if rules.allow.iter().any(|rule| matches(&rule)) {
+            continue;
+        }
+
+        let rule = match rules.deny.iter().find(matches) {
+            Some(rule) => rule.name.clone(),
+            None if rules.default == Policy::Deny => String::from("default deny policy"),
+            None => continue,
+        };
+
+        violations.push(Violation {
+            rule,
+            connector,
+            source,
+            target,
+        });
+    }
+
+    Ok(violations)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::test_support::temp_path;
+
+    fn model() -> Model {
+        serde_json::from_str(
+            r#"{
+                "components": [
+                    {"id": "web", "component_values": {"service": "frontend-web"}},
+                    {"id": "api", "component_values": {"service": "backend-api"}},
+                    {"id": "db", "component_values": {"db": "users"}}
+                ],
+                "connectors": [
+                    {"connector_type": "HTTP", "source_component_id": "web", "target_component_id": "api"},
+                    {"connector_type": "SQL", "source_component_id": "web", "target_component_id": "db"},
+                    {"connector_type": "SQL", "source_component_id": "api", "target_component_id": "db"}
+                ]
+            }"#,
+        )
+        .unwrap()
+    }
+
+    fn rules(yaml: &str) -> ArchitectureRules {
+        let rule_file: ArchitectureRuleFile = serde_yaml::from_str(yaml).unwrap();
+        ArchitectureRules {
+            default: rule_file.default,
+            allow: compile_rules(rule_file.allow, "allow").unwrap(),
+            deny: compile_rules(rule_file.deny, "deny").unwrap(),
+        }
+    }
+
+    fn violations(model: &Model, rules: &ArchitectureRules) -> Vec<(String, String, String)> {
+        check_model(model, rules)
+            .unwrap()
+            .into_iter()
+            .map(|violation| {
+                (
+                    violation.rule,
+                    violation.source.id.clone(),
+                    violation.target.id.clone(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn reports_connectors_that_match_deny_rules() {
+        let rules = rules(
+            "version: 1\n\
+             deny:\n\
+             - name: no direct database access\n  from: { service: \"frontend*\" }\n  to: { db: \"*\" }\n\
+             - to: { db: \"*\" }\n  connectorType: HTTP\n",
+        );
+
+        assert_eq!(
+            violations(&model(), &rules),
+            [(
+                String::from("no direct database access"),
+                String::from("web"),
+                String::from("db")
+            )]
+        );
+    }
+
+    #[test]
+    fn allow_rules_are_exceptions_to_deny_rules() {
+        let rules = rules(
+            "version: 1\n\
+             allow:\n\
+             - from: { service: \"frontend-web\" }\n  connectorType: SQL\n\
+             deny:\n\
+             - to: { db: \"*\" }\n",
+        );
+
+        assert_eq!(
+            violations(&model(), &rules),
+            [(
+                String::from("deny rule #1"),
+                String::from("api"),
+                String::from("db")
+            )]
+        );
+    }
+
+    #[test]
+    fn default_deny_policy_requires_an_allow_rule() {
+        let rules = rules(
+            "version: 1\n\
+             default: deny\n\
+             allow:\n\
+             - from: { service: \"*\" }\n  to: { service: \"*\" }\n",
+        );
+
+        let rule = String::from("default deny policy");
+        assert_eq!(
+            violations(&model(), &rules),
+            [
+                (rule.clone(), String::from("web"), String::from("db")),
+                (rule, String::from("api"), String::from("db")),
+            ]
+        );
+    }
+
+    #[test]
+    fn selectors_require_every_identifier() {
+        let rules = rules("version: 1\ndeny:\n- from: { service: \"*\", host: \"*\" }\n");
+        assert!(violations(&model(), &rules).is_empty());
+    }
+
+    #[test]
+    fn rejects_unknown_fields() {
+        for yaml in [
+            "version: 1\ndenny:\n- to: { db: \"*\" }\n",
+            "version: 1\ndeny:\n- too: { db: \"*\" }\n",
+        ] {
+            assert!(
+                serde_yaml::from_str::<ArchitectureRuleFile>(yaml).is_err(),
+                "{:?} was parsed",
+                yaml
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_connectors_with_unknown_components() {
+        let mut model = model();
+        model.connectors[2].target_component_id = String::from("unknown");
+
+        let rules = rules("version: 1\n");
+        assert!(matches!(
+            check_model(&model, &rules),
+            Err(CheckError::UnknownComponent(id)) if id == "unknown"
+        ));
+    }
+
+    #[test]
+    fn rejects_other_versions() {
+        let path = temp_path("architecture.json");
+        fs::write(&path, r#"{"version": 2}"#).unwrap();
+        let error = read_rules(&path).err().unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert!(matches!(
+            error.downcast_ref::<CheckError>(),
+            Some(CheckError::UnsupportedVersion(2))
+        ));
+    }
+}
@@ -1,143 +1,543 @@
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap},
     error::Error,
     fmt::{self, Display, Formatter},
     fs::OpenOptions,
-    io::{BufRead, BufReader, Write},
+    io::{self, BufWriter, Write},
     path::Path,
+    str::FromStr,
 };
 
 use serde::{Deserialize, Serialize};
 
-use crate::model::mapping_rules::MappingRule;
+use crate::{
+    model::mapping_rules::MappingRule,
+    trace::{ExecutionTrace, TraceLocation},
+};
+
+/// A file path that stands for the standard input or output.
+pub const STDIO_PATH: &str = "-";
 
 #[derive(Serialize, Deserialize, Debug)]
-pub struct ExecutionTrace {
-    pub id: String, // <MappingRuleId,Procedure,Index>
+pub struct Ci {
+    pub id: String,
+
+    /// The procedure in the execution trace ID
+    #[serde(default)]
+    pub procedure: String,
+
+    /// The index in the execution trace ID, which orders calls within the procedure
+    #[serde(default)]
+    pub index: u64,
+
+    pub connector_type: String,
+
+    /// Values of the source component identifier schema, which identify the source component
+    pub source_component_values: HashMap<String, String>,
+
+    /// The other source values, which describe the execution context of the source component
+    #[serde(default)]
+    pub source_context_values: HashMap<String, String>,
+
+    /// Values of the target component identifier schema, which identify the target component
+    pub target_component_values: HashMap<String, String>,
 
-    #[serde(rename = "sourceValues")]
-    pub source_values: HashMap<String, String>,
+    /// The other target values
+    #[serde(default)]
+    pub target_context_values: HashMap<String, String>,
 
-    #[serde(rename = "targetValues")]
-    pub target_values: HashMap<String, String>,
+    /// Where the execution trace of the connector instance was read from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub location: Option<TraceLocation>,
 }
 
-pub fn read_execution_traces(file_path_str: String) -> Result<Vec<ExecutionTrace>, Box<dyn Error>> {
-    let file_path = Path::new(&file_path_str);
-    let execution_traces_file = OpenOptions::new().read(true).open(file_path)?;
-    let reader = BufReader::new(execution_traces_file);
-    let mut execution_traces: Vec<ExecutionTrace> = Vec::new();
-    for line in reader.lines().flatten() {
-        let execution_trace: ExecutionTrace = match serde_json::from_str(&line) {
-            Ok(execution_trace) => execution_trace,
-            Err(_) => {
+/// What to do with execution traces that match no mapping rule, i.e., execution traces with
+/// malformed IDs, unknown mapping rule IDs or procedures different from their mapping rules.
+pub enum UnmatchedPolicy {
+    /// Fails the whole run.
+    Fail,
+    /// Skips the execution traces.
+    Skip,
+    /// Skips the execution traces and writes them as JSON Lines to the file.
+    Collect(String),
+}
+
+#[derive(Debug, Default)]
+pub struct CiSummary {
+    pub created: usize,
+
+    /// The number of unmatched execution traces per mapping rule ID in their execution trace IDs.
+    pub unmatched: BTreeMap<String, usize>,
+}
+
+/// Creates connector instances from execution traces one by one and writes each of them as soon as
+/// it is created, so that memory use does not grow with the number of execution traces.
+pub fn create_cis<I>(
+    execution_traces: I,
+    mapping_rules: &[MappingRule],
+    writer: &mut CiWriter,
+    unmatched_policy: &UnmatchedPolicy,
+    keep_context: bool,
+) -> Result<CiSummary, Box<dyn Error>>
+where
+    I: Iterator<Item = Result<ExecutionTrace, Box<dyn Error>>>,
+{
+    let mut unmatched_writer = match unmatched_policy {
+        UnmatchedPolicy::Collect(p) => Some(BufWriter::new(
+            OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .open(p)?,
+        )),
+        _ => None,
+    };
+    let mut summary = CiSummary::default();
+
+    for execution_trace in execution_traces {
+        let execution_trace = execution_trace?;
+        let matched = execution_trace
+            .id
+            .parse::<ExecutionTraceId>()
+            .and_then(|id| Ok((find_corresponding_mapping_rule(mapping_rules, &id)?, id)));
+        let (mapping_rule, id) = match matched {
+            Ok(matched) => matched,
+            Err(_) if !matches!(unmatched_policy, UnmatchedPolicy::Fail) => {
+                let mapping_rule_id = get_mapping_rule_id(&execution_trace.id);
+                *summary.unmatched.entry(mapping_rule_id).or_insert(0) += 1;
+
+                if let Some(unmatched_writer) = &mut unmatched_writer {
+                    serde_json::to_writer(&mut *unmatched_writer, &execution_trace)?;
+                    unmatched_writer.write_all(b"\n")?;
+                }
                 continue;
             }
+            Err(e) => {
+                return Err(Box::new(CIError::AtLocation(
+                    Box::new(e),
+                    execution_trace.location,
+                )))
+            }
         };
-        execution_traces.push(execution_trace);
+
+        writer.write(&create_ci(execution_trace, id, mapping_rule, keep_context))?;
+        summary.created += 1;
     }
 
-    Ok(execution_traces)
-}
+    if let Some(unmatched_writer) = &mut unmatched_writer {
+        unmatched_writer.flush()?;
+    }
 
-#[derive(Serialize, Deserialize, Debug)]
-pub struct Ci {
-    pub id: String,
-    pub connector_type: String,
-    pub source_component_values: HashMap<String, String>,
-    // pub additional_source_component_values: HashMap<String, String>,
-    pub target_component_values: HashMap<String, String>,
+    Ok(summary)
 }
 
-pub fn create_cis(
-    execution_traces: Vec<ExecutionTrace>,
-    mapping_rules: Vec<MappingRule>,
-) -> Result<Vec<Ci>, Box<dyn Error>> {
-    let mut cis: Vec<Ci> = Vec::new();
+fn create_ci(
+    execution_trace: ExecutionTrace,
+    id: ExecutionTraceId,
+    mapping_rule: &MappingRule,
+    keep_context: bool,
+) -> Ci {
+    // Mapping rules without a source schema identify source components by all source values
+    // (execution context), as they did before source schemas were applied.
+    let source_schema = &mapping_rule.source_component_identifier_schema;
+    let (source_component_values, source_context_values) = split_values(
+        execution_trace.source_values,
+        (!source_schema.is_empty()).then_some(source_schema.as_slice()),
+        keep_context,
+    );
+    let (target_component_values, target_context_values) = split_values(
+        execution_trace.target_values,
+        Some(&mapping_rule.target_component_identifier_schema),
+        keep_context,
+    );
 
-    for execution_trace in execution_traces {
-        let mapping_rule = find_corresponding_mapping_rule(&mapping_rules, &execution_trace.id)?;
+    Ci {
+        id: execution_trace.id,
+        procedure: id.procedure,
+        index: id.index,
+        connector_type: mapping_rule.connector_type.clone(),
+        source_component_values,
+        source_context_values,
+        target_component_values,
+        target_context_values,
+        location: Some(execution_trace.location),
+    }
+}
 
-        // 모든 Source Value 들을 기록해두어야, 나중에 찾을 수 있음 (execution context)
-        let mut source_component_values: HashMap<String, String> = HashMap::new();
-        for (key, value) in execution_trace.source_values {
-            if !value.is_empty() {
-                source_component_values.insert(key, value);
-            }
+/// Splits non-empty values into identity values of the identifier schema and context values. All
+/// values are identity values if no schema is given. Context values are dropped unless
+/// `keep_context` is set.
+fn split_values(
+    values: HashMap<String, String>,
+    schema: Option<&[String]>,
+    keep_context: bool,
+) -> (HashMap<String, String>, HashMap<String, String>) {
+    let mut identity_values: HashMap<String, String> = HashMap::new();
+    let mut context_values: HashMap<String, String> = HashMap::new();
+
+    for (key, value) in values {
+        if value.is_empty() {
+            continue;
         }
 
-        let mut target_component_values: HashMap<String, String> = HashMap::new();
-        for identifier in mapping_rule.target_component_identifier_schema {
-            let value = execution_trace.target_values.get(&identifier);
-            if let Some(value) = value {
-                if !value.is_empty() {
-                    target_component_values.insert(identifier, value.clone());
-                }
-            }
+        if schema.is_none_or(|schema| schema.contains(&key)) {
+            identity_values.insert(key, value);
+        } else if keep_context {
+            context_values.insert(key, value);
         }
+    }
 
-        let ci = Ci {
-            id: execution_trace.id.clone(),
-            connector_type: mapping_rule.connector_type.clone(),
-            source_component_values,
-            // additional_source_component_values,
-            target_component_values,
-        };
+    (identity_values, context_values)
+}
 
-        cis.push(ci);
-    }
+/// A parsed execution trace ID of the form `<MappingRuleId>_<Procedure>_<Index>`. The procedure may
+/// contain underscores.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExecutionTraceId {
+    pub mapping_rule_id: String,
+    pub procedure: String,
+    pub index: u64,
+}
 
-    Ok(cis)
+impl FromStr for ExecutionTraceId {
+    type Err = CIError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let malformed = || CIError::MalformedExecutionTraceId(s.to_string());
+
+        let (mapping_rule_id, rest) = s.split_once('_').ok_or_else(malformed)?;
+        let (procedure, index) = rest.rsplit_once('_').ok_or_else(malformed)?;
+        if mapping_rule_id.is_empty() || procedure.is_empty() {
+            return Err(malformed());
+        }
+
+        Ok(Self {
+            mapping_rule_id: mapping_rule_id.to_string(),
+            procedure: procedure.to_string(),
+            index: index.parse().map_err(|_| malformed())?,
+        })
+    }
 }
 
-fn find_corresponding_mapping_rule(
-    mapping_rules: &[MappingRule],
-    execution_trace_id: &str,
-) -> Result<MappingRule, CIError> {
-    let mapping_rule_id = get_mapping_rule_id(execution_trace_id)?;
+fn find_corresponding_mapping_rule<'a>(
+    mapping_rules: &'a [MappingRule],
+    execution_trace_id: &ExecutionTraceId,
+) -> Result<&'a MappingRule, CIError> {
     let mapping_rule = mapping_rules
         .iter()
         .find(|mapping_rule| {
             if let Some(id) = mapping_rule.id {
-                id.to_hex() == mapping_rule_id
+                id.to_hex() == execution_trace_id.mapping_rule_id
             } else {
                 false
             }
         })
-        .ok_or(CIError::NoCorrespondingMappingRule)?;
+        .ok_or_else(|| {
+            CIError::NoCorrespondingMappingRule(execution_trace_id.mapping_rule_id.clone())
+        })?;
+
+    if mapping_rule.procedure != execution_trace_id.procedure {
+        return Err(CIError::ProcedureMismatch {
+            expected: mapping_rule.procedure.clone(),
+            actual: execution_trace_id.procedure.clone(),
+        });
+    }
 
-    Ok(mapping_rule.clone())
+    Ok(mapping_rule)
 }
 
-fn get_mapping_rule_id(execution_trace_id: &str) -> Result<String, CIError> {
-    let split: Vec<&str> = execution_trace_id.split('_').collect();
-    if split.is_empty() {
-        return Err(CIError::MalformedExecutionTraceId);
+/// Returns the text before the first underscore of an execution trace ID, which is the mapping
+/// rule ID if the execution trace ID is well-formed.
+fn get_mapping_rule_id(execution_trace_id: &str) -> String {
+    match execution_trace_id.split_once('_') {
+        Some((mapping_rule_id, _)) => mapping_rule_id.to_string(),
+        None => execution_trace_id.to_string(),
     }
+}
 
-    Ok(split[0].to_string())
+/// Writes connector instances as JSON Lines, i.e., one JSON object per line. The output file path
+/// "-" writes to the standard output.
+pub struct CiWriter {
+    writer: BufWriter<Box<dyn Write>>,
 }
 
-pub fn write_cis(cis: Vec<Ci>, output_file_path_str: &str) -> Result<(), Box<dyn Error>> {
-    let p = Path::new(output_file_path_str);
-    let mut file = OpenOptions::new().write(true).create(true).open(p)?;
-    file.write_all(serde_json::to_string_pretty(&cis)?.as_bytes())?;
-    Ok(())
+impl CiWriter {
+    pub fn create(output_file_path_str: &str) -> Result<Self, Box<dyn Error>> {
+        let output: Box<dyn Write> = if output_file_path_str == STDIO_PATH {
+            Box::new(io::stdout())
+        } else {
+            let p = Path::new(output_file_path_str);
+            Box::new(
+                OpenOptions::new()
+                    .write(true)
+                    .create(true)
+                    .truncate(true)
+                    .open(p)?,
+            )
+        };
+
+        Ok(Self {
+            writer: BufWriter::new(output),
+        })
+    }
+
+    pub fn write(&mut self, ci: &Ci) -> Result<(), Box<dyn Error>> {
+        serde_json::to_writer(&mut self.writer, ci)?;
+        self.writer.write_all(b"\n")?;
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> Result<(), Box<dyn Error>> {
+        self.writer.flush()?;
+        Ok(())
+    }
 }
 
 #[derive(Debug)]
-enum CIError {
-    MalformedExecutionTraceId,
-    NoCorrespondingMappingRule,
+pub enum CIError {
+    MalformedExecutionTraceId(String),
+    NoCorrespondingMappingRule(String),
+    ProcedureMismatch { expected: String, actual: String },
+    AtLocation(Box<CIError>, TraceLocation),
 }
 
 impl Error for CIError {}
 
 impl Display for CIError {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
-        match *self {
-            CIError::MalformedExecutionTraceId => write!(f, "Malformed execution trace id"),
-            CIError::NoCorrespondingMappingRule => write!(f, "No corresponding mapping rule"),
+        match self {
+            CIError::MalformedExecutionTraceId(id) => {
+                write!(f, "Malformed execution trace id: {}", id)
+            }
+            CIError::NoCorrespondingMappingRule(id) => {
+                write!(f, "No corresponding mapping rule: {}", id)
+            }
+            CIError::ProcedureMismatch { expected, actual } => write!(
+                f,
+                "Procedure mismatch: expected {}, but found {}",
+                expected, actual
+            ),
+            CIError::AtLocation(e, location) => write!(f, "{} ({})", e, location),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use bson::oid::ObjectId;
+
+    use super::*;
+    use crate::test_support::{self, temp_path, values};
+
+    const RULE_ID: &str = "65f1f77bcf86cd7994390111";
+
+    /// The shared mapping rule with the ID that the execution trace IDs refer to
+    fn mapping_rule() -> MappingRule {
+        MappingRule {
+            id: Some(ObjectId::from_str(RULE_ID).unwrap()),
+            ..test_support::mapping_rule()
+        }
+    }
+
+    fn execution_trace(id: &str, line: usize) -> ExecutionTrace {
+        ExecutionTrace {
+            id: String::from(id),
+            source_values: values(&[("host", "web"), ("pid", "42")]),
+            target_values: values(&[("host", "db"), ("port", "5432"), ("user", "")]),
+            location: TraceLocation {
+                file: String::from("traces.jsonl"),
+                line,
+            },
+        }
+    }
+
+    /// Creates connector instances into a temporary file, and returns them with the summary.
+    fn create(
+        execution_traces: Vec<ExecutionTrace>,
+        unmatched_policy: &UnmatchedPolicy,
+        keep_context: bool,
+    ) -> Result<(CiSummary, Vec<Ci>), Box<dyn Error>> {
+        let path = temp_path("cis.jsonl");
+        let mut writer = CiWriter::create(&path)?;
+        let summary = create_cis(
+            execution_traces.into_iter().map(Ok),
+            &[mapping_rule()],
+            &mut writer,
+            unmatched_policy,
+            keep_context,
+        );
+        writer.finish()?;
+        let content = fs::read_to_string(&path)?;
+        fs::remove_file(&path)?;
+
+        let cis = content
+            .lines()
+            .map(serde_json::from_str)
+            .collect::<Result<Vec<Ci>, _>>()?;
+        Ok((summary?, cis))
+    }
+
+    #[test]
+    fn parses_execution_trace_ids() {
+        let id: ExecutionTraceId = "507f1f77bcf86cd799439011_get_user_42".parse().unwrap();
+        assert_eq!(
+            id,
+            ExecutionTraceId {
+                mapping_rule_id: String::from("507f1f77bcf86cd799439011"),
+                procedure: String::from("get_user"),
+                index: 42,
+            }
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_execution_trace_ids() {
+        for id in [
+            "",
+            "rule",
+            "rule_3",
+            "_call_3",
+            "rule__3",
+            "rule_call_",
+            "rule_call_x",
+            "rule_call_-1",
+        ] {
+            assert!(
+                matches!(
+                    id.parse::<ExecutionTraceId>(),
+                    Err(CIError::MalformedExecutionTraceId(s)) if s == id
+                ),
+                "{:?} was parsed",
+                id
+            );
+        }
+    }
+
+    #[test]
+    fn writes_one_connector_instance_per_line() {
+        let id = format!("{}_get_user_", RULE_ID);
+        let (summary, cis) = create(
+            vec![
+                execution_trace(&format!("{}1", id), 1),
+                execution_trace(&format!("{}2", id), 2),
+            ],
+            &UnmatchedPolicy::Fail,
+            false,
+        )
+        .unwrap();
+
+        assert_eq!(summary.created, 2);
+        assert!(summary.unmatched.is_empty());
+        assert_eq!(cis.len(), 2);
+        assert_eq!(cis[1].id, format!("{}2", id));
+        assert_eq!(cis[1].procedure, "get_user");
+        assert_eq!(cis[1].index, 2);
+        assert_eq!(cis[1].connector_type, "HTTP");
+        assert_eq!(
+            cis[1].location,
+            Some(TraceLocation {
+                file: String::from("traces.jsonl"),
+                line: 2
+            })
+        );
+    }
+
+    /// Two matched execution traces around ones with a malformed ID, an unknown mapping rule and a
+    /// different procedure
+    fn unmatched_traces() -> Vec<ExecutionTrace> {
+        vec![
+            execution_trace(&format!("{}_get_user_1", RULE_ID), 1),
+            execution_trace("malformed", 2),
+            execution_trace("65f1f77bcf86cd7994390999_get_user_1", 3),
+            execution_trace(&format!("{}_put_user_1", RULE_ID), 4),
+            execution_trace(&format!("{}_get_user_2", RULE_ID), 5),
+        ]
+    }
+
+    #[test]
+    fn fails_on_unmatched_execution_traces_with_their_locations() {
+        let error = create(unmatched_traces(), &UnmatchedPolicy::Fail, false).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Malformed execution trace id: malformed (traces.jsonl:2)"
+        );
+
+        let mut execution_traces = unmatched_traces();
+        execution_traces.drain(1..3);
+        let error = create(execution_traces, &UnmatchedPolicy::Fail, false).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Procedure mismatch: expected get_user, but found put_user (traces.jsonl:4)"
+        );
+    }
+
+    #[test]
+    fn skips_or_collects_unmatched_execution_traces() {
+        let (summary, cis) = create(unmatched_traces(), &UnmatchedPolicy::Skip, false).unwrap();
+        assert_eq!(summary.created, 2);
+        assert_eq!(cis.len(), 2);
+        assert_eq!(
+            summary.unmatched,
+            BTreeMap::from([
+                (String::from("65f1f77bcf86cd7994390111"), 1),
+                (String::from("65f1f77bcf86cd7994390999"), 1),
+                (String::from("malformed"), 1),
+            ])
+        );
+
+        let path = temp_path("unmatched.jsonl");
+        let (summary, _) = create(
+            unmatched_traces(),
+            &UnmatchedPolicy::Collect(path.clone()),
+            false,
+        )
+        .unwrap();
+        let collected: Vec<String> = fs::read_to_string(&path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str::<ExecutionTrace>(line).unwrap().id)
+            .collect();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(summary.created, 2);
+        assert_eq!(
+            collected,
+            [
+                "malformed",
+                "65f1f77bcf86cd7994390999_get_user_1",
+                "65f1f77bcf86cd7994390111_put_user_1",
+            ]
+        );
+    }
+
+    #[test]
+    fn splits_identity_values_from_context_values() {
+        let id = format!("{}_get_user_1", RULE_ID);
+        let (_, cis) = create(vec![execution_trace(&id, 1)], &UnmatchedPolicy::Fail, true).unwrap();
+        assert_eq!(cis[0].source_component_values, values(&[("host", "web")]));
+        assert_eq!(cis[0].source_context_values, values(&[("pid", "42")]));
+        // Empty values are dropped.
+        assert_eq!(
+            cis[0].target_component_values,
+            values(&[("host", "db"), ("port", "5432")])
+        );
+        assert!(cis[0].target_context_values.is_empty());
+
+        let (_, cis) =
+            create(vec![execution_trace(&id, 1)], &UnmatchedPolicy::Fail, false).unwrap();
+        assert!(cis[0].source_context_values.is_empty());
+    }
+
+    #[test]
+    fn keeps_every_value_as_an_identity_value_without_a_schema() {
+        let (identity_values, context_values) = split_values(
+            values(&[("host", "web"), ("pid", "42"), ("user", "")]),
+            None,
+            true,
+        );
+        assert_eq!(identity_values, values(&[("host", "web"), ("pid", "42")]));
+        assert!(context_values.is_empty());
+    }
+}
@@ -1,13 +1,14 @@
 use super::{config, model::*};
 use crate::{
-    ci, conn,
+    check, ci, conn, diff, label,
     model::{self},
-    plugin,
+    plugin, trace,
 };
+use bson::oid::ObjectId;
 use clap::{Parser, Subcommand};
-use log::{error, info};
+use log::{error, info, warn};
 
-use std::{error::Error, fmt::Display};
+use std::{collections::BTreeSet, error::Error, fmt::Display};
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
@@ -21,7 +22,7 @@ struct Cli {
 enum Commands {
     /// Set the database URL
     SetDB {
-        /// The database URL
+        /// The database URL. "mongodb://..." uses MongoDB, and "file://<path>" uses an embedded JSON file store.
         db_url: String,
     },
 
@@ -38,6 +39,12 @@ enum Commands {
         name: Option<String>,
     },
 
+    /// Set the label file of the current project, which names and labels components in the outputs of "conn"
+    SetLabels {
+        /// A YAML or JSON label file. If the label file is not provided, the label file of the project is unset.
+        label_file: Option<String>,
+    },
+
     /// Filter dependency relations from source code to external libraries
     Dr {
         #[arg(short, long)]
@@ -55,28 +62,235 @@ enum Commands {
 
     /// Extract connector instances from execution traces
     Ci {
-        #[arg(short, long)]
-        /// A directory path that contains all execution traces
-        execution_traces: String,
+        #[arg(short, long, num_args = 1.., required = true)]
+        /// Execution trace files or directories that contain execution traces. Directories are walked recursively. Gzip and zstd compressed files are supported.
+        execution_traces: Vec<String>,
+
+        #[arg(long)]
+        /// Glob patterns of files to read from directories, e.g., "*.jsonl". All files are read if none are provided.
+        include: Vec<String>,
+
+        #[arg(long)]
+        /// Glob patterns of files to skip in directories
+        exclude: Vec<String>,
 
         #[arg(short, long)]
-        /// An output file path that contains connector instances
+        /// An output file path of connector instances, which are written as JSON Lines. "-" writes to the standard output.
         output_file: String,
+
+        #[arg(long)]
+        /// A YAML or JSON rule file to read mapping rules from instead of the database
+        rules: Option<String>,
+
+        #[arg(long)]
+        /// Fail on the first malformed execution trace line instead of skipping it
+        strict: bool,
+
+        #[arg(long)]
+        /// A file path to which skipped execution trace lines are written
+        quarantine_file: Option<String>,
+
+        #[arg(long, default_value = "fail")]
+        /// What to do with execution traces that match no mapping rule because of an unknown mapping rule ID, a different procedure, or a malformed execution trace ID: "fail", "skip", or "collect"
+        unmatched: String,
+
+        #[arg(long)]
+        /// A file path to which unmatched execution traces are written. Required if --unmatched is "collect".
+        unmatched_file: Option<String>,
+
+        #[arg(long)]
+        /// Keep only the values of the identifier schemas and drop the other values (execution context)
+        no_context: bool,
     },
 
     /// Build an execution view model from connector instances
     Conn {
         #[arg(short, long)]
-        /// A file path that contains connector instances
+        /// A file path that contains connector instances as JSON Lines or a JSON array. "-" reads from the standard input.
         ci_file: String,
 
         #[arg(short, long)]
-        /// An output file path that contains an execution view model
+        /// An output file path that contains an execution view model. "-" writes to the standard output, except for the "png" format.
         output_file: String,
 
         #[arg(short, long)]
-        /// An output format of the execution view model. Currently, "json", "png", and "dot" are supported.
+        /// An output format of the execution view model. Currently, "json", "png", "svg", "dot", "mermaid", "plantuml", "graphml", "gexf", "structurizr", and "html" are supported.
+        format: String,
+
+        #[arg(long)]
+        /// Record the connector instances and execution traces that contributed to each component and connector. They are written only in the JSON format. Memory use grows with the number of connector instances in this mode, since each connector records the trace IDs and evidence of all of its connector instances instead of a sample of trace IDs.
+        provenance: bool,
+
+        #[arg(short, long)]
+        /// A JSON model file to merge the connector instances into. Components of the model keep their IDs.
+        base_model: Option<String>,
+
+        #[arg(long, default_value = "")]
+        /// Identifier keys whose values name components in the "structurizr" format, e.g., "service,host". Comma separated values are allowed. Components are named by all identifier values if none are provided.
+        name_keys: String,
+
+        #[arg(long)]
+        /// Render the "svg" format with the Graphviz "dot" command instead of the built-in layout. The "png" format always needs Graphviz.
+        graphviz: bool,
+
+        #[arg(short, long, default_value = "")]
+        /// Identifier keys to group components by, outermost first, e.g., "host,pid". Groups are drawn as nested clusters in the "dot", "png", and "svg" (with --graphviz) formats, and written as nested groups in the "json" format.
+        group_by: String,
+
+        #[arg(short, long)]
+        /// A YAML or JSON label file that names and labels components in every format. The label file of the current project set by "set-labels" is used if none is provided.
+        labels: Option<String>,
+    },
+
+    /// Print the connector instances and execution traces that produced a component or a connector
+    Explain {
+        #[arg(short, long)]
+        /// A JSON model file built by "conn" with --provenance
+        model_file: String,
+
+        /// A component ID, or a connector as "<source component ID>-><target component ID>"
+        element: String,
+
+        #[arg(short, long)]
+        /// A connector type to explain only connectors of the type
+        connector_type: Option<String>,
+    },
+
+    /// Compare two JSON models. Components are matched by their identifier values, not by their IDs.
+    Diff {
+        /// An old model file
+        old_model: String,
+
+        /// A new model file
+        new_model: String,
+
+        #[arg(short, long, default_value = "-")]
+        /// An output file path. "-" writes to the standard output.
+        output_file: String,
+
+        #[arg(short, long, default_value = "text")]
+        /// An output format: "text", "json", or "dot". The "dot" format draws both models in one graph, in which added elements are green, removed elements are red, and changed components are orange.
         format: String,
+
+        #[arg(short, long)]
+        /// A YAML or JSON label file that names and labels components. The label file of the current project set by "set-labels" is used if none is provided.
+        labels: Option<String>,
+
+        #[arg(long)]
+        /// Also report components whose context values, e.g., process IDs, have changed. Context values usually vary between runs.
+        context: bool,
+    },
+
+    /// Check a JSON model against allow and deny rules of an intended architecture. Exits with status 1 if any connector violates them.
+    Check {
+        #[arg(short, long)]
+        /// A JSON model file
+        model_file: String,
+
+        #[arg(short, long)]
+        /// A YAML or JSON file of architecture rules. The format is chosen by the extension.
+        rules: String,
+    },
+
+    /// Manage mapping rules of the current project
+    Rule {
+        #[command(subcommand)]
+        command: RuleCommands,
+    },
+}
+
+#[derive(Subcommand)]
+enum RuleCommands {
+    /// Add a mapping rule to the current project
+    Add {
+        #[arg(short, long)]
+        /// A procedure that the mapping rule applies to
+        procedure: String,
+
+        #[arg(short, long)]
+        /// A connector type of connector instances created by the mapping rule
+        connector_type: String,
+
+        #[arg(long)]
+        /// A source of the relation that the mapping rule implements
+        relation_source: String,
+
+        #[arg(long)]
+        /// A target of the relation that the mapping rule implements
+        relation_target: String,
+
+        #[arg(long)]
+        /// An ID of the relation. If the relation ID is not provided, a new ID is generated.
+        relation_id: Option<String>,
+
+        #[arg(long, default_value = "")]
+        /// Identifiers of source components. Comma separated values are allowed.
+        source_schema: String,
+
+        #[arg(long)]
+        /// Identifiers of target components. Comma separated values are allowed.
+        target_schema: String,
+    },
+
+    /// List mapping rules of the current project
+    List {},
+
+    /// Show a mapping rule of the current project
+    Show {
+        /// The mapping rule ID
+        id: String,
+    },
+
+    /// Edit a mapping rule of the current project. Only the provided fields are changed.
+    Edit {
+        /// The mapping rule ID
+        id: String,
+
+        #[arg(short, long)]
+        /// A procedure that the mapping rule applies to
+        procedure: Option<String>,
+
+        #[arg(short, long)]
+        /// A connector type of connector instances created by the mapping rule
+        connector_type: Option<String>,
+
+        #[arg(long)]
+        /// A source of the relation that the mapping rule implements
+        relation_source: Option<String>,
+
+        #[arg(long)]
+        /// A target of the relation that the mapping rule implements
+        relation_target: Option<String>,
+
+        #[arg(long)]
+        /// An ID of the relation
+        relation_id: Option<String>,
+
+        #[arg(long)]
+        /// Identifiers of source components. Comma separated values are allowed.
+        source_schema: Option<String>,
+
+        #[arg(long)]
+        /// Identifiers of target components. Comma separated values are allowed.
+        target_schema: Option<String>,
+    },
+
+    /// Delete a mapping rule of the current project
+    Delete {
+        /// The mapping rule ID
+        id: String,
+    },
+
+    /// Import mapping rules into the current project from a YAML or JSON rule file. Mapping rules with the same IDs are replaced.
+    Import {
+        /// A rule file path. The format is chosen by the extension: ".json", ".yaml", or ".yml".
+        file: String,
+    },
+
+    /// Export mapping rules of the current project to a YAML or JSON rule file
+    Export {
+        /// A rule file path. The format is chosen by the extension: ".json", ".yaml", or ".yml".
+        file: String,
     },
 }
 
@@ -86,6 +300,11 @@ enum CmdError {
     NoSuchProject,
     WrongArguments,
     NoProjectIdSet,
+    NoSuchMappingRule,
+    MappingRuleOfOtherProject(String),
+    NoSuchModelElement(String),
+    NoProvenance(String),
+    ArchitectureViolations(usize),
 }
 
 impl Error for CmdError {}
@@ -97,16 +316,41 @@ impl Display for CmdError {
             CmdError::NoSuchProject => write!(f, "No such project"),
             CmdError::WrongArguments => write!(f, "Wrong arguments"),
             CmdError::NoProjectIdSet => write!(f, "No project ID is set"),
+            CmdError::NoSuchMappingRule => write!(f, "No such mapping rule"),
+            CmdError::MappingRuleOfOtherProject(id) => {
+                write!(f, "Mapping rule {} belongs to another project", id)
+            }
+            CmdError::NoSuchModelElement(element) => {
+                write!(f, "No such component or connector: {}", element)
+            }
+            CmdError::NoProvenance(element) => write!(
+                f,
+                "No provenance is recorded for {}. Build the model with --provenance",
+                element
+            ),
+            CmdError::ArchitectureViolations(count) => {
+                write!(f, "Found {} architecture violations", count)
+            }
         }
     }
 }
 
+/// The exit status of `check` when connectors violate the architecture rules
+const ARCHITECTURE_VIOLATIONS_EXIT_STATUS: i32 = 1;
+
 pub async fn init_app() {
     let cli = Cli::parse();
 
     match run_command(cli.command).await {
         Ok(_) => {}
-        Err(e) => error!("{}", e),
+        Err(e) => {
+            error!("{}", e);
+            // Only architecture violations fail the process, so that CI pipelines can gate on
+            // `check`.
+            if let Some(CmdError::ArchitectureViolations(_)) = e.downcast_ref::<CmdError>() {
+                std::process::exit(ARCHITECTURE_VIOLATIONS_EXIT_STATUS);
+            }
+        }
     }
 }
 
@@ -115,6 +359,7 @@ async fn run_command(cmd: Option<Commands>) -> Result<(), Box<dyn Error>> {
         Some(Commands::SetDB { db_url }) => set_db(db_url).await,
         Some(Commands::GetDB {}) => get_db().await,
         Some(Commands::SetProject { project_id, name }) => set_project(project_id, name).await,
+        Some(Commands::SetLabels { label_file }) => set_labels(label_file),
         Some(Commands::Dr {
             root_path,
             lang,
@@ -122,13 +367,85 @@ async fn run_command(cmd: Option<Commands>) -> Result<(), Box<dyn Error>> {
         }) => save_drs(root_path, lang, sources).await,
         Some(Commands::Ci {
             execution_traces,
+            include,
+            exclude,
             output_file,
-        }) => extract_cis(execution_traces, output_file).await,
+            rules,
+            strict,
+            quarantine_file,
+            unmatched,
+            unmatched_file,
+            no_context,
+        }) => {
+            let unmatched_policy = match (unmatched.as_str(), unmatched_file) {
+                ("fail", _) => ci::UnmatchedPolicy::Fail,
+                ("skip", _) => ci::UnmatchedPolicy::Skip,
+                ("collect", Some(file)) => ci::UnmatchedPolicy::Collect(file),
+                ("collect", None) => return Err(Box::new(CmdError::NotEnoughArguments)),
+                _ => return Err(Box::new(CmdError::WrongArguments)),
+            };
+            let paths = TracePaths {
+                execution_traces,
+                include,
+                exclude,
+            };
+            let options = trace::TraceReadOptions {
+                strict,
+                quarantine_file,
+            };
+            let cis_options = CisOptions {
+                unmatched_policy,
+                keep_context: !no_context,
+            };
+            extract_cis(paths, options, cis_options, output_file, rules).await
+        }
         Some(Commands::Conn {
             ci_file,
             output_file,
             format,
-        }) => build_connectors(ci_file, output_file, format),
+            provenance,
+            base_model,
+            name_keys,
+            graphviz,
+            group_by,
+            labels,
+        }) => {
+            let options = conn::WriteOptions {
+                name_keys: split_list(&name_keys),
+                graphviz,
+                group_by: split_list(&group_by),
+                labels: read_label_rules(labels)?,
+            };
+            build_connectors(
+                ci_file,
+                output_file,
+                format,
+                provenance,
+                base_model,
+                options,
+            )
+        }
+        Some(Commands::Explain {
+            model_file,
+            element,
+            connector_type,
+        }) => explain(model_file, element, connector_type),
+        Some(Commands::Diff {
+            old_model,
+            new_model,
+            output_file,
+            format,
+            labels,
+            context,
+        }) => {
+            let old = conn::read_model(&old_model)?;
+            let new = conn::read_model(&new_model)?;
+            let labels = read_label_rules(labels)?;
+            let diff = diff::diff_models(&old, &new, labels.as_ref(), context)?;
+            diff::write_diff(&diff, &output_file, &format)
+        }
+        Some(Commands::Check { model_file, rules }) => check_architecture(model_file, rules),
+        Some(Commands::Rule { command }) => run_rule_command(command).await,
         None => {
             error!("No command provided");
             Ok(())
@@ -137,7 +454,7 @@ async fn run_command(cmd: Option<Commands>) -> Result<(), Box<dyn Error>> {
 }
 
 async fn set_db(db_url: String) -> Result<(), Box<dyn Error>> {
-    mongo::get_mongo_client(&db_url).await?; // Check if the URL is valid
+    store::open(&db_url).await?; // Check if the URL is valid
 
     let mut config = config::read()?;
 
@@ -165,6 +482,9 @@ async fn get_db() -> Result<(), Box<dyn Error>> {
     };
 
     s.push_str(&format!("project_id: {}\n", &project_id));
+    if let Some(label_file) = config.label_files.get(&project_id) {
+        s.push_str(&format!("label_file: {}\n", label_file));
+    }
 
     if !config.db_url.is_empty() {
         let projects = projects::read_many(&config.db_url).await?;
@@ -217,6 +537,51 @@ async fn set_project(
     Ok(())
 }
 
+fn set_labels(label_file: Option<String>) -> Result<(), Box<dyn Error>> {
+    let mut config = config::read()?;
+    let project_id = config.project_id.clone().ok_or(CmdError::NoProjectIdSet)?;
+
+    match label_file {
+        Some(label_file) => {
+            label::read_labels(&label_file)?; // Check if the label file is valid
+
+            // The configuration is shared by every working directory.
+            let path = std::fs::canonicalize(&label_file)?;
+            config
+                .label_files
+                .insert(project_id, path.display().to_string());
+        }
+        None => {
+            config.label_files.remove(&project_id);
+        }
+    }
+
+    config::write(&config)?;
+
+    Ok(())
+}
+
+/// Reads the label file, or the label file of the current project if none is provided.
+fn read_label_rules(
+    label_file: Option<String>,
+) -> Result<Option<label::LabelRules>, Box<dyn Error>> {
+    let label_file = match label_file {
+        Some(label_file) => label_file,
+        None => {
+            let config = config::read()?;
+            let label_file = config
+                .project_id
+                .and_then(|id| config.label_files.get(&id).cloned());
+            match label_file {
+                Some(label_file) => label_file,
+                None => return Ok(None),
+            }
+        }
+    };
+
+    Ok(Some(label::read_labels(&label_file)?))
+}
+
 async fn save_drs(root_path: String, lang: String, sources: String) -> Result<(), Box<dyn Error>> {
     let config = config::read()?;
     let project_id = match config.project_id {
@@ -264,27 +629,561 @@ fn is_start_with(item: &str, sources: &Vec<&str>) -> bool {
     false
 }
 
+struct CisOptions {
+    unmatched_policy: ci::UnmatchedPolicy,
+    keep_context: bool,
+}
+
+struct TracePaths {
+    execution_traces: Vec<String>,
+    include: Vec<String>,
+    exclude: Vec<String>,
+}
+
 async fn extract_cis(
-    execution_traces_file_path_str: String,
+    paths: TracePaths,
+    options: trace::TraceReadOptions,
+    cis_options: CisOptions,
     output_file_path_str: String,
+    rule_file_path_str: Option<String>,
 ) -> Result<(), Box<dyn Error>> {
     let config = config::read()?;
-    let project_id = config.project_id.ok_or(CmdError::NoProjectIdSet)?;
+    let mapping_rules = read_mapping_rules(config, rule_file_path_str.as_deref()).await?;
+
+    let files =
+        trace::find_execution_trace_files(&paths.execution_traces, &paths.include, &paths.exclude)?;
+    let mut execution_traces = trace::read_execution_traces(files, options)?;
 
-    let execution_traces = ci::read_execution_traces(execution_traces_file_path_str)?;
-    let mapping_rules = model::mapping_rules::read_many(&config.db_url, &project_id).await?;
+    let mut writer = ci::CiWriter::create(&output_file_path_str)?;
+    let result = ci::create_cis(
+        &mut execution_traces,
+        &mapping_rules,
+        &mut writer,
+        &cis_options.unmatched_policy,
+        cis_options.keep_context,
+    )
+    .and_then(|summary| writer.finish().map(|_| summary));
 
-    let cis = ci::create_cis(execution_traces, mapping_rules)?;
+    // Lines skipped before an error are reported too, so that the error is not the only output.
+    let diagnostics = execution_traces.finish();
+    if let Ok(diagnostics) = &diagnostics {
+        report_trace_diagnostics(diagnostics);
+    }
+    let summary = result?;
+    diagnostics?;
+    report_unmatched_execution_traces(&summary);
 
-    ci::write_cis(cis, &output_file_path_str)
+    info!("Extracted {} connector instances", summary.created);
+    Ok(())
+}
+
+/// Reads mapping rules from a rule file if one is given, and otherwise from the database. Rule
+/// files do not need a project, since their mapping rules are never written to the database.
+async fn read_mapping_rules(
+    config: config::Config,
+    rule_file_path_str: Option<&str>,
+) -> Result<Vec<mapping_rules::MappingRule>, Box<dyn Error>> {
+    match rule_file_path_str {
+        Some(rule_file_path_str) => {
+            let project_id = config.project_id.unwrap_or_default();
+            rule_file::read(rule_file_path_str, &project_id)
+        }
+        None => {
+            let project_id = config.project_id.ok_or(CmdError::NoProjectIdSet)?;
+            model::mapping_rules::read_many(&config.db_url, &project_id).await
+        }
+    }
+}
+
+fn report_unmatched_execution_traces(summary: &ci::CiSummary) {
+    if summary.unmatched.is_empty() {
+        return;
+    }
+
+    let mut s = format!(
+        "Skipped {} execution traces that match no mapping rule:\n",
+        summary.unmatched.values().sum::<usize>()
+    );
+    for (mapping_rule_id, count) in &summary.unmatched {
+        s.push_str(&format!("    {}: {}\n", mapping_rule_id, count));
+    }
+
+    warn!("{}", s.trim_end());
+}
+
+fn report_trace_diagnostics(diagnostics: &trace::TraceDiagnostics) {
+    let skipped_count = diagnostics.skipped_count();
+    if skipped_count == 0 {
+        return;
+    }
+
+    let mut s = format!(
+        "Skipped {} of {} execution trace lines:\n",
+        skipped_count, diagnostics.read_lines
+    );
+    for (kind, skipped) in &diagnostics.skipped_lines {
+        s.push_str(&format!(
+            "    {}: {} (first at {})\n",
+            kind, skipped.count, skipped.first
+        ));
+    }
+
+    warn!("{}", s.trim_end());
 }
 
 fn build_connectors(
     ci_file: String,
     output_file: String,
     output_format: String,
+    provenance: bool,
+    base_model: Option<String>,
+    options: conn::WriteOptions,
 ) -> Result<(), Box<dyn Error>> {
     let cis = conn::read_cis(&ci_file)?;
-    let model = conn::build_model(cis)?;
-    conn::write_model(model, &output_file, &output_format)
+    let model = match base_model {
+        Some(base_model) => {
+            let base = conn::read_model(&base_model)?;
+            let (model, report) = conn::merge_model(base, cis, provenance)?;
+            report_merge(&report);
+            model
+        }
+        None => conn::build_model(cis, provenance)?,
+    };
+
+    for conflict in &model.conflicts {
+        warn!(
+            "Component {} has conflicting values for {}: {}",
+            conflict.component_id,
+            conflict.identifier,
+            conflict.values.join(", ")
+        );
+    }
+
+    conn::write_model(model, &output_file, &output_format, &options)
+}
+
+fn report_merge(report: &conn::MergeReport) {
+    let mut s = format!(
+        "Merged into the base model:\n    components: {} added, {} extended, {} untouched\n    connectors: {} added, {} extended, {} untouched\n",
+        report.added_components.len(),
+        report.extended_components.len(),
+        report.untouched_components.len(),
+        report.added_connectors,
+        report.extended_connectors,
+        report.untouched_connectors,
+    );
+    if !report.added_components.is_empty() {
+        s.push_str(&format!(
+            "    added: {}\n",
+            report.added_components.join(", ")
+        ));
+    }
+    if !report.extended_components.is_empty() {
+        s.push_str(&format!(
+            "    extended: {}\n",
+            report.extended_components.join(", ")
+        ));
+    }
+    info!("{}", s.trim_end());
+
+    for ids in &report.merged_components {
+        warn!(
+            "Components {} have turned out to be one component, which keeps the ID {}",
+            ids.join(", "),
+            ids[0]
+        );
+    }
+}
+
+fn check_architecture(model_file: String, rules_file: String) -> Result<(), Box<dyn Error>> {
+    let model = conn::read_model(&model_file)?;
+    let rules = check::read_rules(&rules_file)?;
+    let violations = check::check_model(&model, &rules)?;
+
+    if violations.is_empty() {
+        info!(
+            "No architecture violations in {} connectors",
+            model.connectors.len()
+        );
+        return Ok(());
+    }
+
+    let mut s = String::new();
+    for violation in &violations {
+        let connector = violation.connector;
+        s.push_str(&format!("violation: {}\n", violation.rule));
+        s.push_str(&format!(
+            "connector: {} -> {} [{}]\n",
+            connector.source_component_id, connector.target_component_id, connector.connector_type
+        ));
+        s.push_str(&format!(
+            "source: {} ({})\n",
+            violation.source.id,
+            conn::format_values(&violation.source.component_values)
+        ));
+        s.push_str(&format!(
+            "target: {} ({})\n",
+            violation.target.id,
+            conn::format_values(&violation.target.component_values)
+        ));
+        s.push_str(&format!("count: {}\n", connector.count));
+        if !connector.evidence.is_empty() {
+            push_evidence(&mut s, &connector.evidence);
+        } else {
+            // Without provenance, connectors only have a sample of their trace IDs.
+            let (name, trace_ids) = if connector.trace_ids.is_empty() {
+                ("trace_id_sample", &connector.trace_id_sample)
+            } else {
+                ("trace_ids", &connector.trace_ids)
+            };
+            if !trace_ids.is_empty() {
+                s.push_str(&format!(
+                    "{}: {}\n",
+                    name,
+                    trace_ids.iter().cloned().collect::<Vec<_>>().join(", ")
+                ));
+            }
+        }
+        s.push('\n');
+    }
+
+    println!("{}", s.trim_end());
+    Err(Box::new(CmdError::ArchitectureViolations(violations.len())))
+}
+
+fn explain(
+    model_file: String,
+    element: String,
+    connector_type: Option<String>,
+) -> Result<(), Box<dyn Error>> {
+    let model = conn::read_model(&model_file)?;
+    let component_of = |id: &str| model.components.iter().find(|c| c.id == id);
+
+    let mut s = String::new();
+    match element.split_once("->") {
+        None => {
+            let component =
+                component_of(&element).ok_or(CmdError::NoSuchModelElement(element.clone()))?;
+            if component.evidence.is_empty() {
+                return Err(Box::new(CmdError::NoProvenance(element)));
+            }
+
+            s.push_str(&format!("component: {}\n", component.id));
+            s.push_str(&format!(
+                "component_values: {}\n",
+                conn::format_values(&component.component_values)
+            ));
+            push_evidence(&mut s, &component.evidence);
+        }
+        Some((source, target)) => {
+            let (source, target) = (source.trim(), target.trim());
+            let connectors: Vec<&conn::Connector> = model
+                .connectors
+                .iter()
+                .filter(|c| c.source_component_id == source && c.target_component_id == target)
+                .filter(|c| {
+                    connector_type
+                        .as_ref()
+                        .is_none_or(|t| &c.connector_type == t)
+                })
+                .collect();
+            if connectors.is_empty() {
+                return Err(Box::new(CmdError::NoSuchModelElement(element)));
+            }
+            if connectors.iter().all(|c| c.evidence.is_empty()) {
+                return Err(Box::new(CmdError::NoProvenance(element)));
+            }
+
+            for connector in connectors {
+                s.push_str(&format!(
+                    "connector: {} -> {} [{}]\n",
+                    source, target, connector.connector_type
+                ));
+                for (role, id) in [("source", source), ("target", target)] {
+                    let values = component_of(id)
+                        .map(|c| conn::format_values(&c.component_values))
+                        .unwrap_or_default();
+                    s.push_str(&format!("{}: {} ({})\n", role, id, values));
+                }
+                s.push_str(&format!("count: {}\n", connector.count));
+                s.push_str(&format!(
+                    "procedures: {}\n",
+                    connector
+                        .procedures
+                        .iter()
+                        .cloned()
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ));
+                push_evidence(&mut s, &connector.evidence);
+            }
+        }
+    }
+
+    println!("{}", s);
+    Ok(())
+}
+
+fn push_evidence(s: &mut String, evidence: &BTreeSet<conn::Evidence>) {
+    s.push_str(&format!("evidence ({}):\n", evidence.len()));
+    for e in evidence {
+        s.push_str(&format!("    {}\n", e));
+    }
+}
+
+async fn run_rule_command(cmd: RuleCommands) -> Result<(), Box<dyn Error>> {
+    let config = config::read()?;
+    let project_id = config.project_id.ok_or(CmdError::NoProjectIdSet)?;
+
+    match cmd {
+        RuleCommands::Add {
+            procedure,
+            connector_type,
+            relation_source,
+            relation_target,
+            relation_id,
+            source_schema,
+            target_schema,
+        } => {
+            let mapping_rule = mapping_rules::MappingRule {
+                id: None,
+                project_id: project_id.clone(),
+                procedure,
+                relation: mapping_rules::Relation {
+                    id: relation_id.unwrap_or_else(|| ObjectId::new().to_hex()),
+                    source: relation_source,
+                    target: relation_target,
+                    project_id,
+                },
+                connector_type,
+                source_component_identifier_schema: split_list(&source_schema),
+                target_component_identifier_schema: split_list(&target_schema),
+            };
+
+            let id = mapping_rules::create(&config.db_url, mapping_rule).await?;
+            println!("{}", id);
+            Ok(())
+        }
+        RuleCommands::List {} => {
+            let rules = mapping_rules::read_many(&config.db_url, &project_id).await?;
+
+            let mut s = String::from("mapping_rules:\n");
+            for rule in rules {
+                let id = match rule.id {
+                    Some(id) => id.to_hex(),
+                    None => "".to_string(),
+                };
+
+                s.push_str(&format!(
+                    "    {}: {} [{}] {} -> {}\n",
+                    id,
+                    rule.procedure,
+                    rule.connector_type,
+                    rule.relation.source,
+                    rule.relation.target,
+                ));
+            }
+
+            println!("{}", s);
+            Ok(())
+        }
+        RuleCommands::Show { id } => {
+            let rule = read_project_mapping_rule(&config.db_url, &project_id, &id).await?;
+
+            let mut s = String::new();
+            s.push_str(&format!("id: {}\n", id));
+            s.push_str(&format!("procedure: {}\n", rule.procedure));
+            s.push_str(&format!("connector_type: {}\n", rule.connector_type));
+            s.push_str(&format!(
+                "relation: {} ({} -> {})\n",
+                rule.relation.id, rule.relation.source, rule.relation.target
+            ));
+            s.push_str(&format!(
+                "source_component_identifier_schema: {}\n",
+                rule.source_component_identifier_schema.join(", ")
+            ));
+            s.push_str(&format!(
+                "target_component_identifier_schema: {}\n",
+                rule.target_component_identifier_schema.join(", ")
+            ));
+
+            println!("{}", s);
+            Ok(())
+        }
+        RuleCommands::Edit {
+            id,
+            procedure,
+            connector_type,
+            relation_source,
+            relation_target,
+            relation_id,
+            source_schema,
+            target_schema,
+        } => {
+            let mut rule = read_project_mapping_rule(&config.db_url, &project_id, &id).await?;
+
+            if let Some(procedure) = procedure {
+                rule.procedure = procedure;
+            }
+            if let Some(connector_type) = connector_type {
+                rule.connector_type = connector_type;
+            }
+            if let Some(relation_source) = relation_source {
+                rule.relation.source = relation_source;
+            }
+            if let Some(relation_target) = relation_target {
+                rule.relation.target = relation_target;
+            }
+            if let Some(relation_id) = relation_id {
+                rule.relation.id = relation_id;
+            }
+            if let Some(source_schema) = source_schema {
+                rule.source_component_identifier_schema = split_list(&source_schema);
+            }
+            if let Some(target_schema) = target_schema {
+                rule.target_component_identifier_schema = split_list(&target_schema);
+            }
+
+            mapping_rules::update(&config.db_url, rule).await
+        }
+        RuleCommands::Delete { id } => {
+            read_project_mapping_rule(&config.db_url, &project_id, &id).await?;
+
+            match mapping_rules::delete(&config.db_url, &id).await? {
+                true => Ok(()),
+                false => Err(Box::new(CmdError::NoSuchMappingRule)),
+            }
+        }
+        RuleCommands::Import { file } => {
+            // Mapping rules have been validated by reading the rule file.
+            let rules = rule_file::read(&file, &project_id)?;
+
+            // One store serves every lookup and write, instead of one connection per call.
+            let store = store::open(&config.db_url).await?;
+            for rule in &rules {
+                let id = rule.id.map(|id| id.to_hex()).unwrap_or_default();
+                if let Some(existing) = store.read_mapping_rule(&id).await? {
+                    if existing.project_id != project_id {
+                        return Err(Box::new(CmdError::MappingRuleOfOtherProject(id)));
+                    }
+                }
+            }
+
+            let count = rules.len();
+            for rule in rules {
+                mapping_rules::upsert(store.as_ref(), rule).await?;
+            }
+
+            info!("Imported {} mapping rules", count);
+            Ok(())
+        }
+        RuleCommands::Export { file } => {
+            let rules = mapping_rules::read_many(&config.db_url, &project_id).await?;
+            rule_file::write(&file, &rules)?;
+
+            info!("Exported {} mapping rules", rules.len());
+            Ok(())
+        }
+    }
+}
+
+async fn read_project_mapping_rule(
+    db_url: &str,
+    project_id: &str,
+    id: &str,
+) -> Result<mapping_rules::MappingRule, Box<dyn Error>> {
+    match mapping_rules::read_one(db_url, id).await? {
+        Some(rule) if rule.project_id == project_id => Ok(rule),
+        _ => Err(Box::new(CmdError::NoSuchMappingRule)),
+    }
+}
+
+fn split_list(s: &str) -> Vec<String> {
+    if s.trim().is_empty() {
+        return Vec::new();
+    }
+
+    s.split(',').map(|item| item.trim().to_string()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, fs};
+
+    use futures::executor::block_on;
+
+    use super::*;
+    use crate::test_support::{self, temp_path, values};
+
+    const RULE_ID: &str = "65f1f77bcf86cd7994390111";
+
+    fn config_without_project() -> config::Config {
+        config::Config {
+            db_url: String::new(),
+            project_id: None,
+            label_files: Default::default(),
+        }
+    }
+
+    fn execution_trace(id: &str) -> trace::ExecutionTrace {
+        trace::ExecutionTrace {
+            id: String::from(id),
+            source_values: values(&[("host", "web")]),
+            target_values: values(&[("host", "db"), ("port", "5432")]),
+            location: Default::default(),
+        }
+    }
+
+    #[test]
+    fn creates_connector_instances_with_rule_files_and_no_project() {
+        let rule_file_path = temp_path("rules.yaml");
+        let mut mapping_rule = test_support::mapping_rule();
+        mapping_rule.id = Some(ObjectId::parse_str(RULE_ID).unwrap());
+        rule_file::write(&rule_file_path, &[mapping_rule]).unwrap();
+
+        let mapping_rules = block_on(read_mapping_rules(
+            config_without_project(),
+            Some(&rule_file_path),
+        ));
+        fs::remove_file(&rule_file_path).unwrap();
+        let mapping_rules = mapping_rules.unwrap();
+        assert_eq!(mapping_rules.len(), 1);
+        assert!(mapping_rules[0].project_id.is_empty());
+
+        let cis_path = temp_path("cis.jsonl");
+        let mut writer = ci::CiWriter::create(&cis_path).unwrap();
+        let summary = ci::create_cis(
+            [
+                execution_trace(&format!("{}_get_user_1", RULE_ID)),
+                execution_trace(&format!("{}_get_user_2", RULE_ID)),
+            ]
+            .into_iter()
+            .map(Ok),
+            &mapping_rules,
+            &mut writer,
+            &ci::UnmatchedPolicy::Fail,
+            false,
+        )
+        .unwrap();
+        writer.finish().unwrap();
+        let content = fs::read_to_string(&cis_path).unwrap();
+        fs::remove_file(&cis_path).unwrap();
+
+        assert_eq!(summary.created, 2);
+        let ci: ci::Ci = serde_json::from_str(content.lines().next().unwrap()).unwrap();
+        assert_eq!(ci.connector_type, "HTTP");
+        assert_eq!(
+            ci.target_component_values,
+            values::<HashMap<_, _>>(&[("host", "db"), ("port", "5432")])
+        );
+    }
+
+    #[test]
+    fn requires_a_project_without_rule_files() {
+        let error = block_on(read_mapping_rules(config_without_project(), None)).unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<CmdError>(),
+            Some(CmdError::NoProjectIdSet)
+        ));
+    }
 }
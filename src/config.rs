@@ -1,6 +1,7 @@
 use serde::{Deserialize, Serialize};
 
 use std::{
+    collections::BTreeMap,
     error::Error,
     fs::{self, OpenOptions},
     io::{Read, Write},
@@ -11,6 +12,10 @@ use std::{
 pub struct Config {
     pub db_url: String,
     pub project_id: Option<String>,
+
+    /// Label files by project ID, which name and label components of the project
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub label_files: BTreeMap<String, String>,
 }
 
 const SAREX_DIR: &str = ".sarex";
@@ -31,7 +36,11 @@ pub fn read() -> Result<Config, Box<dyn Error>> {
 pub fn write(c: &Config) -> Result<(), Box<dyn Error>> {
     let p = get_path()?;
 
-    let mut file = OpenOptions::new().write(true).create(true).open(p)?;
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(p)?;
     file.write_all(serde_json::to_string(&c)?.as_bytes())?;
 
     Ok(())
@@ -55,6 +64,7 @@ fn create_new_config() -> Result<Config, Box<dyn Error>> {
     let config = Config {
         db_url: String::from(""),
         project_id: None,
+        label_files: BTreeMap::new(),
     };
 
     write(&config)?;
@@ -67,6 +77,7 @@ fn read_existing_config(p: &PathBuf) -> Result<Config, Box<dyn Error>> {
         .read(true)
         .write(true)
         .create(true)
+        .truncate(false)
         .open(p)?;
 
     let mut content = String::new();
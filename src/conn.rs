@@ -1,194 +1,671 @@
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
     error::Error,
+    fmt::{self, Display, Formatter},
     fs::OpenOptions,
-    io::{BufReader, Write},
-    iter,
+    io::{self, BufRead, BufReader, Write},
     path::Path,
 };
 
 use graphviz_rust::{cmd::CommandArg, dot_structures::*, printer::DotPrinter};
 use graphviz_rust::{cmd::Format, dot_generator::*, exec, printer::PrinterContext};
-use rand::Rng;
 use serde::{Deserialize, Serialize};
 
-use crate::ci::Ci;
+use crate::{
+    ci::{Ci, STDIO_PATH},
+    identity::{IdentifierValues, IdentityResolver},
+    label::{self, LabelRules},
+    trace::TraceLocation,
+    writer::{gexf, graphml, html, mermaid, plantuml, structurizr, svg},
+};
+
+#[derive(Debug)]
+pub enum ConnError {
+    UnknownComponent(String),
+    GraphvizNotFound(String),
+}
 
-pub fn read_cis(file_path_str: &str) -> Result<Vec<Ci>, Box<dyn Error>> {
-    let file_path = Path::new(file_path_str);
-    let cis_file = OpenOptions::new().read(true).open(file_path)?;
-    let reader = BufReader::new(cis_file);
-    let cis: Vec<Ci> = serde_json::from_reader(reader)?;
+impl Error for ConnError {}
 
-    Ok(cis)
+impl Display for ConnError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            ConnError::UnknownComponent(id) => {
+                write!(f, "A connector refers to an unknown component: {}", id)
+            }
+            ConnError::GraphvizNotFound(format) => write!(
+                f,
+                "The \"{}\" format needs the Graphviz \"dot\" command, which is not found. Install Graphviz, or use the \"svg\" format with the built-in layout, which needs no external programs.",
+                format
+            ),
+        }
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct Model {
-    pub connectors: Vec<Connector>,
-    pub components: Vec<Component>,
+/// A stream of connector instances read by `read_cis`
+pub type CiStream = Box<dyn Iterator<Item = Result<Ci, Box<dyn Error>>>>;
+
+/// Reads connector instances written as JSON Lines by the `ci` command one by one. A JSON array of
+/// connector instances is also accepted, but it is read at once. The file path "-" reads from the
+/// standard input.
+pub fn read_cis(file_path_str: &str) -> Result<CiStream, Box<dyn Error>> {
+    let mut reader: Box<dyn BufRead> = if file_path_str == STDIO_PATH {
+        Box::new(BufReader::new(io::stdin()))
+    } else {
+        let file_path = Path::new(file_path_str);
+        Box::new(BufReader::new(
+            OpenOptions::new().read(true).open(file_path)?,
+        ))
+    };
+
+    if is_json_array(&mut reader)? {
+        let cis: Vec<Ci> = serde_json::from_reader(reader)?;
+        return Ok(Box::new(cis.into_iter().map(Ok)));
+    }
+
+    let cis = reader.lines().filter_map(|line| match line {
+        Ok(line) if line.trim().is_empty() => None,
+        Ok(line) => Some(serde_json::from_str::<Ci>(&line).map_err(|e| e.into())),
+        Err(e) => Some(Err(e.into())),
+    });
+
+    Ok(Box::new(cis))
 }
 
-impl Model {
-    fn new() -> Self {
-        Self {
-            connectors: Vec::new(),
-            components: Vec::new(),
+/// Reads a model written by `write_model` in the JSON format.
+pub fn read_model(file_path_str: &str) -> Result<Model, Box<dyn Error>> {
+    let file = OpenOptions::new()
+        .read(true)
+        .open(Path::new(file_path_str))?;
+    let model: Model = serde_json::from_reader(BufReader::new(file))?;
+
+    Ok(model)
+}
+
+fn is_json_array(reader: &mut Box<dyn BufRead>) -> Result<bool, io::Error> {
+    loop {
+        let buf = reader.fill_buf()?;
+        if buf.is_empty() {
+            return Ok(false);
         }
-    }
 
-    fn has_connector(&self, connector: &Connector) -> bool {
-        for c in &self.connectors {
-            if c.connector_type == connector.connector_type
-                && c.source_component_id == connector.source_component_id
-                && c.target_component_id == connector.target_component_id
-            {
-                return true;
+        match buf.iter().position(|b| !b.is_ascii_whitespace()) {
+            Some(i) => {
+                let is_array = buf[i] == b'[';
+                reader.consume(i);
+                return Ok(is_array);
+            }
+            None => {
+                let len = buf.len();
+                reader.consume(len);
             }
         }
-
-        false
     }
 }
 
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Model {
+    pub connectors: Vec<Connector>,
+    pub components: Vec<Component>,
+
+    /// Identifiers for which a component has been observed with more than one value
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub conflicts: Vec<Conflict>,
+
+    /// Components grouped by the values of grouping keys, e.g., hosts and then processes. Only
+    /// written if grouping keys are given.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub groups: Vec<Group>,
+}
+
+/// Components that share the value of a grouping key. Components that also have the value of
+/// the next grouping key are in nested groups, and the others are listed in the group itself.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Group {
+    pub key: String,
+    pub value: String,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub component_ids: Vec<String>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub groups: Vec<Group>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Connector {
     pub connector_type: String,
     pub source_component_id: String,
     pub target_component_id: String,
+
+    /// The number of connector instances that have produced the connector
+    #[serde(default)]
+    pub count: usize,
+
+    /// The smallest distinct execution trace IDs of the connector instances, at most
+    /// `TRACE_ID_SAMPLE_SIZE` of them
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub trace_id_sample: BTreeSet<String>,
+
+    /// Distinct execution trace IDs of the connector instances. Only recorded in the provenance
+    /// mode, since every execution trace ID has its own index and they grow with the number of
+    /// connector instances.
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub trace_ids: BTreeSet<String>,
+
+    /// Distinct procedures of the connector instances
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub procedures: BTreeSet<String>,
+
+    /// Connector instances that produced the connector. Only recorded in the provenance mode.
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub evidence: BTreeSet<Evidence>,
+}
+
+/// The maximum number of trace IDs in the trace ID sample of a connector
+pub const TRACE_ID_SAMPLE_SIZE: usize = 5;
+
+/// Statistics of the connector instances observed for a connector
+#[derive(Debug, Default)]
+struct ConnectorInstances {
+    count: usize,
+    trace_id_sample: BTreeSet<String>,
+    trace_ids: BTreeSet<String>,
+    procedures: BTreeSet<String>,
+    evidence: BTreeSet<Evidence>,
+}
+
+impl ConnectorInstances {
+    fn add(&mut self, trace_id: String, procedure: String, provenance: bool) {
+        self.count += 1;
+        if provenance {
+            self.trace_ids.insert(trace_id.clone());
+        }
+        self.trace_id_sample.insert(trace_id);
+        self.truncate_trace_id_sample();
+        if !procedure.is_empty() {
+            self.procedures.insert(procedure);
+        }
+    }
+
+    fn merge(&mut self, other: ConnectorInstances) {
+        self.count += other.count;
+        self.trace_id_sample.extend(other.trace_id_sample);
+        self.truncate_trace_id_sample();
+        self.trace_ids.extend(other.trace_ids);
+        self.procedures.extend(other.procedures);
+        self.evidence.extend(other.evidence);
+    }
+
+    /// Keeps the smallest trace IDs, so that the sample does not depend on the order of the
+    /// connector instances.
+    fn truncate_trace_id_sample(&mut self) {
+        while self.trace_id_sample.len() > TRACE_ID_SAMPLE_SIZE {
+            self.trace_id_sample.pop_last();
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Component {
     pub id: String,
-    pub component_values: HashMap<String, String>,
+
+    /// Identifier values, which identify the component
+    pub component_values: BTreeMap<String, String>,
+
+    /// A human-friendly name or a label filled from a template, which output formats draw
+    /// instead of the identifier values. Labels are computed from the label file whenever a model
+    /// is written, so labels in model files are never read back.
+    #[serde(skip_deserializing, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+
+    /// Every context value observed with the component. They are metadata and never used to
+    /// identify the component.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub context_values: BTreeMap<String, BTreeSet<String>>,
+
+    /// Connector instances in which the component appears. Only recorded in the provenance mode.
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub evidence: BTreeSet<Evidence>,
 }
 
-pub fn build_model(cis: Vec<Ci>) -> Result<Model, Box<dyn Error>> {
-    let mut model = Model::new();
+/// A connector instance that contributed to a component or a connector, and the execution trace
+/// that it was created from
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Evidence {
+    pub ci_id: String,
 
-    for ci in &cis {
-        // Create source components first.
-        find_or_create_component_if_not_exist(&mut model, ci, true);
-    }
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub location: Option<TraceLocation>,
+}
 
-    for ci in cis {
-        let source_component_id = find_or_create_component_if_not_exist(&mut model, &ci, true);
-        let target_component_id = find_or_create_component_if_not_exist(&mut model, &ci, false);
-
-        create_connector_if_not_exist(
-            &mut model,
-            ci.connector_type,
-            source_component_id,
-            target_component_id,
-        );
+impl Display for Evidence {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match &self.location {
+            Some(location) => write!(f, "{} at {}", self.ci_id, location),
+            None => write!(f, "{}", self.ci_id),
+        }
     }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Conflict {
+    pub component_id: String,
+    pub identifier: String,
+    pub values: Vec<String>,
+}
+
+/// Builds a model from connector instances. Components are resolved by `IdentityResolver`, so the
+/// model does not depend on the order of the connector instances. Connector instances are kept
+/// as hash indexes of distinct identifier values and distinct (type, source, target) connector
+/// keys, and each connector counts its connector instances and records their distinct procedures
+/// and a sample of their trace IDs. Memory use and the size of the model therefore grow with the
+/// number of distinct components and connectors rather than connector instances.
+///
+/// In the provenance mode, every connector also records the trace IDs of all of its connector
+/// instances, and every component and connector records the connector instances that contributed
+/// to it and where their execution traces were read from. Since every connector instance has its
+/// own trace ID, this grows with the number of connector instances, so it is off by default.
+pub fn build_model<I>(cis: I, provenance: bool) -> Result<Model, Box<dyn Error>>
+where
+    I: IntoIterator<Item = Result<Ci, Box<dyn Error>>>,
+{
+    let (model, _) = merge_model(Model::default(), cis, provenance)?;
 
     Ok(model)
 }
 
-fn find_or_create_component_if_not_exist(model: &mut Model, ci: &Ci, is_source: bool) -> String {
-    let component_values = if is_source {
-        &ci.source_component_values
-    } else {
-        &ci.target_component_values
-    };
+/// What merging connector instances into a base model changed
+#[derive(Debug, Default)]
+pub struct MergeReport {
+    /// Components that are not in the base model
+    pub added_components: Vec<String>,
 
-    let component_id = find_component_in_model(model, component_values);
-    if let Some(component) = component_id {
-        component
-    } else {
-        let new_component_id = get_random_id(10);
-        let new_component_values = component_values.clone();
-        // let mut new_component_values = component_values.clone();
-        // if is_source {
-        //     new_component_values.extend(ci.additional_source_component_values.clone());
-        // }
-
-        model.components.push(Component {
-            id: new_component_id.clone(),
-            component_values: new_component_values,
-        });
+    /// Base components that have been observed with new identifier values
+    pub extended_components: Vec<String>,
 
-        new_component_id
-    }
+    /// Base components without new identifier values
+    pub untouched_components: Vec<String>,
+
+    /// Base components that new connector instances have revealed to be one component. The
+    /// first ID is kept and the others are dropped.
+    pub merged_components: Vec<Vec<String>>,
+
+    pub added_connectors: usize,
+
+    /// Base connectors that new connector instances have produced again
+    pub extended_connectors: usize,
+
+    pub untouched_connectors: usize,
 }
 
-fn find_component_in_model(
-    model: &mut Model,
-    component_values: &HashMap<String, String>,
-) -> Option<String> {
-    for component in &mut model.components {
-        let is_component_large = component.component_values.len() > component_values.len();
-        let (large_values, small_values) = if is_component_large {
-            (&component.component_values, component_values)
-        } else {
-            (component_values, &component.component_values)
+/// Merges connector instances into a base model as `build_model` does. Base components are
+/// resolved together with the new connector instances, and keep their IDs, so that a model can
+/// grow over several runs. Statistics, context values and provenance of the base model are kept.
+pub fn merge_model<I>(
+    base: Model,
+    cis: I,
+    provenance: bool,
+) -> Result<(Model, MergeReport), Box<dyn Error>>
+where
+    I: IntoIterator<Item = Result<Ci, Box<dyn Error>>>,
+{
+    let mut resolver = IdentityResolver::default();
+    let mut context_values: HashMap<usize, BTreeMap<String, BTreeSet<String>>> = HashMap::new();
+    let mut evidence: HashMap<usize, BTreeSet<Evidence>> = HashMap::new();
+    let mut connector_keys: HashMap<(String, usize, usize), ConnectorInstances> = HashMap::new();
+
+    let mut base_known_values: HashMap<String, HashSet<(String, String)>> = HashMap::new();
+    for conflict in &base.conflicts {
+        let known_values = base_known_values
+            .entry(conflict.component_id.clone())
+            .or_default();
+        for value in &conflict.values {
+            known_values.insert((conflict.identifier.clone(), value.clone()));
+        }
+    }
+
+    let mut base_value_sets: HashMap<String, usize> = HashMap::new();
+    for component in base.components {
+        let value_set = resolver.add(component.component_values.clone());
+
+        // Conflicting values have been resolved by the base model and only one of them is kept
+        // in the component values, so the others are linked explicitly.
+        for (identifier, value) in base_known_values.get(&component.id).into_iter().flatten() {
+            let mut values = component.component_values.clone();
+            values.insert(identifier.clone(), value.clone());
+            let conflicting_value_set = resolver.add(values);
+            resolver.link(value_set, conflicting_value_set);
+        }
+
+        base_known_values
+            .entry(component.id.clone())
+            .or_default()
+            .extend(component.component_values);
+        for (key, value) in component.context_values {
+            context_values
+                .entry(value_set)
+                .or_default()
+                .entry(key)
+                .or_default()
+                .extend(value);
+        }
+        evidence
+            .entry(value_set)
+            .or_default()
+            .extend(component.evidence);
+        base_value_sets.insert(component.id, value_set);
+    }
+
+    for connector in base.connectors {
+        let value_set_of = |id: &String| {
+            base_value_sets
+                .get(id)
+                .copied()
+                .ok_or_else(|| ConnError::UnknownComponent(id.clone()))
         };
+        let source = value_set_of(&connector.source_component_id)?;
+        let target = value_set_of(&connector.target_component_id)?;
 
-        let mut is_same = true;
-        for (identifier, value) in small_values {
-            if !value.is_empty() {
-                let component_value: Option<&String> = large_values.get(identifier);
-                is_same = is_same && component_value == Some(value);
-            }
+        connector_keys
+            .entry((connector.connector_type, source, target))
+            .or_default()
+            .merge(ConnectorInstances {
+                count: connector.count,
+                trace_id_sample: connector.trace_id_sample,
+                trace_ids: connector.trace_ids,
+                procedures: connector.procedures,
+                evidence: connector.evidence,
+            });
+    }
+    let base_counts: Vec<((String, usize, usize), usize)> = connector_keys
+        .iter()
+        .map(|(key, instances)| (key.clone(), instances.count))
+        .collect();
+
+    for ci in cis {
+        let ci = ci?;
+        let source = resolver.add(to_identifier_values(ci.source_component_values));
+        let target = resolver.add(to_identifier_values(ci.target_component_values));
+
+        add_context_values(
+            context_values.entry(source).or_default(),
+            ci.source_context_values,
+        );
+        add_context_values(
+            context_values.entry(target).or_default(),
+            ci.target_context_values,
+        );
+
+        let instances = connector_keys
+            .entry((ci.connector_type, source, target))
+            .or_default();
+        if provenance {
+            let ci_evidence = Evidence {
+                ci_id: ci.id.clone(),
+                location: ci.location,
+            };
+            evidence
+                .entry(source)
+                .or_default()
+                .insert(ci_evidence.clone());
+            evidence
+                .entry(target)
+                .or_default()
+                .insert(ci_evidence.clone());
+            instances.evidence.insert(ci_evidence);
         }
+        instances.add(ci.id, ci.procedure, provenance);
+    }
+
+    let identities = resolver.resolve();
+    let mut report = MergeReport::default();
+
+    let mut base_ids: Vec<Vec<String>> = vec![Vec::new(); identities.identities.len()];
+    for (id, value_set) in &base_value_sets {
+        base_ids[identities.identity_of(*value_set)].push(id.clone());
+    }
+
+    let mut components: Vec<Component> = Vec::new();
+    let mut conflicts: Vec<Conflict> = Vec::new();
+    let mut used_ids: HashSet<String> = base_value_sets.into_keys().collect();
+    for (identity, mut ids) in identities.identities.iter().zip(base_ids) {
+        ids.sort();
+
+        let id = match ids.first() {
+            Some(id) => {
+                let is_known = |identifier: &String, value: &String| {
+                    ids.iter().any(|id| {
+                        base_known_values[id].contains(&(identifier.clone(), value.clone()))
+                    })
+                };
+                let has_new_values = identity.values.iter().any(|(i, v)| !is_known(i, v))
+                    || identity
+                        .conflicts
+                        .iter()
+                        .any(|(i, values)| values.iter().any(|v| !is_known(i, v)));
+
+                if ids.len() > 1 {
+                    report.merged_components.push(ids.clone());
+                }
+                if has_new_values || ids.len() > 1 {
+                    report.extended_components.push(id.clone());
+                } else {
+                    report.untouched_components.push(id.clone());
+                }
 
-        if is_same {
-            if !is_component_large {
-                for (identifier, value) in component_values {
-                    if !value.is_empty() {
-                        let component_value: Option<&String> =
-                            component.component_values.get(identifier);
-                        if component_value.is_none() {
-                            component
-                                .component_values
-                                .insert(identifier.clone(), value.clone());
-                        }
-                    }
+                id.clone()
+            }
+            None => {
+                let mut salt = 0;
+                let mut id = get_component_id(&identity.values, salt);
+                while used_ids.contains(&id) {
+                    // Resolve a hash collision deterministically.
+                    salt += 1;
+                    id = get_component_id(&identity.values, salt);
                 }
+                used_ids.insert(id.clone());
+                report.added_components.push(id.clone());
+
+                id
             }
+        };
+
+        for (identifier, values) in &identity.conflicts {
+            conflicts.push(Conflict {
+                component_id: id.clone(),
+                identifier: identifier.clone(),
+                values: values.iter().cloned().collect(),
+            });
+        }
+
+        components.push(Component {
+            id,
+            component_values: identity.values.clone(),
+            label: None,
+            context_values: BTreeMap::new(),
+            evidence: BTreeSet::new(),
+        });
+    }
+
+    for (value_set, values) in context_values {
+        let component = &mut components[identities.identity_of(value_set)];
+        for (key, value) in values {
+            component
+                .context_values
+                .entry(key)
+                .or_default()
+                .extend(value);
+        }
+    }
+
+    for (value_set, value_set_evidence) in evidence {
+        components[identities.identity_of(value_set)]
+            .evidence
+            .extend(value_set_evidence);
+    }
+
+    let resolve_key = |(connector_type, source, target): (String, usize, usize)| {
+        (
+            connector_type,
+            identities.identity_of(source),
+            identities.identity_of(target),
+        )
+    };
+    let mut base_connector_counts: HashMap<(String, usize, usize), usize> = HashMap::new();
+    for (key, count) in base_counts {
+        *base_connector_counts.entry(resolve_key(key)).or_insert(0) += count;
+    }
+
+    let mut merged: BTreeMap<(String, usize, usize), ConnectorInstances> = BTreeMap::new();
+    for (key, instances) in connector_keys {
+        merged.entry(resolve_key(key)).or_default().merge(instances);
+    }
 
-            return Some(component.id.clone());
+    for (key, instances) in &merged {
+        match base_connector_counts.get(key) {
+            None => report.added_connectors += 1,
+            Some(count) if instances.count > *count => report.extended_connectors += 1,
+            Some(_) => report.untouched_connectors += 1,
         }
     }
 
-    None
+    let mut connectors: Vec<Connector> = merged
+        .into_iter()
+        .map(|((connector_type, source, target), instances)| Connector {
+            connector_type,
+            source_component_id: components[source].id.clone(),
+            target_component_id: components[target].id.clone(),
+            count: instances.count,
+            trace_id_sample: instances.trace_id_sample,
+            trace_ids: instances.trace_ids,
+            procedures: instances.procedures,
+            evidence: instances.evidence,
+        })
+        .collect();
+    connectors.sort_by(|a, b| {
+        (
+            &a.source_component_id,
+            &a.target_component_id,
+            &a.connector_type,
+        )
+            .cmp(&(
+                &b.source_component_id,
+                &b.target_component_id,
+                &b.connector_type,
+            ))
+    });
+
+    report.added_components.sort();
+    report.extended_components.sort();
+    report.untouched_components.sort();
+
+    Ok((
+        Model {
+            connectors,
+            components,
+            conflicts,
+            groups: Vec::new(),
+        },
+        report,
+    ))
 }
 
-fn get_random_id(len: usize) -> String {
-    const CHARSET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ";
-    let mut rng = rand::thread_rng();
-    let one_char = || CHARSET[rng.gen_range(0..CHARSET.len())] as char;
-    iter::repeat_with(one_char).take(len).collect()
+fn to_identifier_values(values: HashMap<String, String>) -> IdentifierValues {
+    values
+        .into_iter()
+        .filter(|(_, value)| !value.is_empty())
+        .collect()
 }
 
-fn create_connector_if_not_exist(
-    model: &mut Model,
-    connector_type: String,
-    source_component_id: String,
-    target_component_id: String,
+fn add_context_values(
+    context_values: &mut BTreeMap<String, BTreeSet<String>>,
+    values: HashMap<String, String>,
 ) {
-    let new_connector = Connector {
-        connector_type,
-        source_component_id,
-        target_component_id,
+    for (key, value) in values {
+        context_values.entry(key).or_default().insert(value);
+    }
+}
+
+/// Derives a component ID of uppercase letters from a stable FNV-1a hash of the sorted identifier
+/// values. Unlike `std::hash`, the hash never changes between runs, platforms and Rust versions.
+fn get_component_id(component_values: &BTreeMap<String, String>, salt: u32) -> String {
+    const CHARSET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+    const ID_LEN: usize = 10;
+    const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+    const FNV_PRIME: u64 = 0x100000001b3;
+
+    let mut hash = FNV_OFFSET_BASIS;
+    let mut write = |bytes: &[u8]| {
+        for byte in bytes {
+            hash ^= *byte as u64;
+            hash = hash.wrapping_mul(FNV_PRIME);
+        }
     };
 
-    if !model.has_connector(&new_connector) {
-        model.connectors.push(new_connector);
+    // Lengths are hashed as well, so that different values never produce the same byte sequence.
+    for (identifier, value) in component_values {
+        write(&(identifier.len() as u64).to_le_bytes());
+        write(identifier.as_bytes());
+        write(&(value.len() as u64).to_le_bytes());
+        write(value.as_bytes());
+    }
+    write(&salt.to_le_bytes());
+
+    let mut id = String::with_capacity(ID_LEN);
+    for _ in 0..ID_LEN {
+        id.push(CHARSET[(hash % CHARSET.len() as u64) as usize] as char);
+        hash /= CHARSET.len() as u64;
     }
+
+    id
+}
+
+/// Options of output formats that do not apply to every format
+#[derive(Debug, Default)]
+pub struct WriteOptions {
+    /// Identifier keys whose values name components, e.g., Structurizr containers
+    pub name_keys: Vec<String>,
+
+    /// Whether to render the "svg" format with the Graphviz "dot" command instead of the
+    /// built-in layout
+    pub graphviz: bool,
+
+    /// Identifier keys to group components by, outermost first, e.g., "host" and then "pid"
+    pub group_by: Vec<String>,
+
+    /// Names and label templates of components
+    pub labels: Option<LabelRules>,
 }
 
 pub fn write_model(
-    model: Model,
+    mut model: Model,
     output_file_path_str: &str,
     output_format_str: &str,
+    options: &WriteOptions,
 ) -> Result<(), Box<dyn Error>> {
+    if !options.group_by.is_empty() {
+        model.groups = group_components(&model.components, &options.group_by);
+    }
+    label::apply_labels(&mut model, options.labels.as_ref());
+
     match output_format_str {
         "json" => write_model_as_json(model, output_file_path_str)?,
-        "png" => write_model_as_png(model, output_file_path_str)?,
+        "png" => write_model_with_graphviz(model, output_file_path_str, Format::Png)?,
+        "svg" if options.graphviz => {
+            write_model_with_graphviz(model, output_file_path_str, Format::Svg)?
+        }
+        "svg" => print_result_str(&svg::get_svg(&model), output_file_path_str)?,
         "dot" => write_model_as_dot(model, output_file_path_str)?,
+        "mermaid" => print_result_str(&mermaid::get_mermaid(&model), output_file_path_str)?,
+        "plantuml" => print_result_str(&plantuml::get_plantuml(&model), output_file_path_str)?,
+        "graphml" => print_result_str(&graphml::get_graphml(&model), output_file_path_str)?,
+        "gexf" => print_result_str(&gexf::get_gexf(&model), output_file_path_str)?,
+        "html" => print_result_str(&html::get_html(&model)?, output_file_path_str)?,
+        "structurizr" => print_result_str(
+            &structurizr::get_structurizr(&model, &options.name_keys),
+            output_file_path_str,
+        )?,
         _ => write_model_as_json(model, output_file_path_str)?,
     }
 
@@ -200,16 +677,26 @@ fn write_model_as_json(model: Model, output_file_path_str: &str) -> Result<(), B
     print_result_str(&result, output_file_path_str)
 }
 
-fn write_model_as_png(model: Model, output_file_path_str: &str) -> Result<(), Box<dyn Error>> {
-    match exec(
-        get_dot_graph(model),
-        &mut PrinterContext::default(),
-        vec![
-            Format::Png.into(),
-            CommandArg::Output(output_file_path_str.to_string()),
-        ],
-    ) {
+/// Renders a model with the Graphviz "dot" command, which must be installed.
+fn write_model_with_graphviz(
+    model: Model,
+    output_file_path_str: &str,
+    format: Format,
+) -> Result<(), Box<dyn Error>> {
+    // Only text formats are written to the standard output through the result of "dot".
+    let to_stdout = output_file_path_str == STDIO_PATH && matches!(format, Format::Svg);
+    let format_name = format!("{:?}", format).to_lowercase();
+    let mut args = vec![format.into()];
+    if !to_stdout {
+        args.push(CommandArg::Output(output_file_path_str.to_string()));
+    }
+
+    match exec(get_dot_graph(model), &mut PrinterContext::default(), args) {
+        Ok(result) if to_stdout => print_result_str(&result, output_file_path_str),
         Ok(_) => Ok(()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            Err(Box::new(ConnError::GraphvizNotFound(format_name)))
+        }
         Err(e) => Err(Box::new(e)),
     }
 }
@@ -219,9 +706,19 @@ fn write_model_as_dot(model: Model, output_file_path_str: &str) -> Result<(), Bo
     print_result_str(&result, output_file_path_str)
 }
 
-fn print_result_str(result: &str, file_path_str: &str) -> Result<(), Box<dyn Error>> {
+/// Writes a result to a file. The file path "-" writes to the standard output.
+pub(crate) fn print_result_str(result: &str, file_path_str: &str) -> Result<(), Box<dyn Error>> {
+    if file_path_str == STDIO_PATH {
+        io::stdout().write_all(result.as_bytes())?;
+        return Ok(());
+    }
+
     let p = Path::new(file_path_str);
-    let mut file = OpenOptions::new().write(true).create(true).open(p)?;
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(p)?;
 
     match file.write_all(result.as_bytes()) {
         Ok(_) => Ok(()),
@@ -229,25 +726,128 @@ fn print_result_str(result: &str, file_path_str: &str) -> Result<(), Box<dyn Err
     }
 }
 
+/// Groups components by the values of the first key, and each group by the values of the
+/// following keys. Components without a value of a key stay out of the groups of the key.
+pub fn group_components(components: &[Component], keys: &[String]) -> Vec<Group> {
+    let components: Vec<&Component> = components.iter().collect();
+    group_by_keys(&components, keys).0
+}
+
+/// Returns groups of the components by the first key, and IDs of the components that have no
+/// value of the key.
+fn group_by_keys(components: &[&Component], keys: &[String]) -> (Vec<Group>, Vec<String>) {
+    let Some((key, rest)) = keys.split_first() else {
+        return (
+            Vec::new(),
+            components.iter().map(|c| c.id.clone()).collect(),
+        );
+    };
+
+    let mut by_value: BTreeMap<&str, Vec<&Component>> = BTreeMap::new();
+    let mut ungrouped: Vec<String> = Vec::new();
+    for component in components {
+        match component.component_values.get(key) {
+            Some(value) if !value.is_empty() => by_value.entry(value).or_default().push(component),
+            _ => ungrouped.push(component.id.clone()),
+        }
+    }
+
+    let groups = by_value
+        .into_iter()
+        .map(|(value, members)| {
+            let (groups, component_ids) = group_by_keys(&members, rest);
+            Group {
+                key: key.clone(),
+                value: value.to_string(),
+                component_ids,
+                groups,
+            }
+        })
+        .collect();
+
+    (groups, ungrouped)
+}
+
 fn get_dot_graph(model: Model) -> Graph {
     let mut g = graph!(di id!("model"));
 
+    // Nodes are declared in the clusters of their groups, or at the top level if ungrouped.
+    let mut nodes: BTreeMap<String, Stmt> = BTreeMap::new();
+    let mut ids: Vec<String> = Vec::with_capacity(model.components.len());
     for component in model.components {
-        let label = get_node_label(&component.component_values);
+        let label = match &component.label {
+            Some(label) => quote_dot(label),
+            None => get_node_label(&component.component_values),
+        };
         let node = node!(component.id;attr!("label", &label));
-        g.add_stmt(stmt!(node));
+        ids.push(component.id.clone());
+        nodes.insert(component.id, stmt!(node));
+    }
+    for (index, group) in model.groups.iter().enumerate() {
+        g.add_stmt(get_dot_cluster(group, &index.to_string(), &mut nodes));
+    }
+    for id in ids {
+        if let Some(node) = nodes.remove(&id) {
+            g.add_stmt(node);
+        }
     }
 
+    let max_count = model.connectors.iter().map(|c| c.count).max().unwrap_or(0);
     for connector in model.connectors {
-        let label = get_edge_label(&connector.connector_type);
-        let edge = edge!(node_id!(connector.source_component_id) => node_id!(connector.target_component_id);attr!("label", &label));
+        let label = get_edge_label(&connector.connector_type, connector.count);
+        let weight = connector.count.max(1).to_string();
+        let penwidth = format!("{:.2}", get_edge_penwidth(connector.count, max_count));
+        let edge = edge!(node_id!(connector.source_component_id) => node_id!(connector.target_component_id);
+            attr!("label", &label),
+            attr!("weight", &weight),
+            attr!("penwidth", &penwidth));
         g.add_stmt(stmt!(edge));
     }
 
     g
 }
 
-fn get_node_label(component_values: &HashMap<String, String>) -> String {
+/// Draws a group as a cluster, whose ID is made of the indexes of the group and its ancestors,
+/// since Graphviz only draws subgraphs whose IDs start with "cluster" as boxes.
+fn get_dot_cluster(group: &Group, path: &str, nodes: &mut BTreeMap<String, Stmt>) -> Stmt {
+    let label = quote_dot(&format!("{}:{}", group.key, group.value));
+    let mut stmts: Vec<Stmt> = vec![stmt!(attr!("label", &label))];
+    for (index, nested) in group.groups.iter().enumerate() {
+        stmts.push(get_dot_cluster(
+            nested,
+            &format!("{}_{}", path, index),
+            nodes,
+        ));
+    }
+    stmts.extend(group.component_ids.iter().filter_map(|id| nodes.remove(id)));
+
+    Stmt::Subgraph(Subgraph {
+        id: Id::Plain(format!("cluster_{}", path)),
+        stmts,
+    })
+}
+
+/// Quotes text as a DOT string.
+pub(crate) fn quote_dot(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Lines of the label of a component, which are its label if it has one, or its non-empty
+/// identifier values as "identifier:value"
+pub(crate) fn get_label_lines(component: &Component) -> Vec<String> {
+    if let Some(label) = &component.label {
+        return vec![label.clone()];
+    }
+
+    component
+        .component_values
+        .iter()
+        .filter(|(_, value)| !value.is_empty())
+        .map(|(identifier, value)| format!("{}:{}", identifier, value))
+        .collect()
+}
+
+pub(crate) fn get_node_label(component_values: &BTreeMap<String, String>) -> String {
     let mut label: String = String::from("\"");
     for (identifier, value) in component_values {
         if !value.is_empty() {
@@ -259,6 +859,479 @@ fn get_node_label(component_values: &HashMap<String, String>) -> String {
     label
 }
 
-fn get_edge_label(connector_type: &str) -> String {
-    format!("\"{}\"", connector_type)
+/// Formats identifier values as "identifier:value, ..." for text outputs.
+pub(crate) fn format_values(values: &BTreeMap<String, String>) -> String {
+    values
+        .iter()
+        .map(|(identifier, value)| format!("{}:{}", identifier, value))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+pub(crate) fn get_edge_label(connector_type: &str, count: usize) -> String {
+    if count == 0 {
+        return format!("\"{}\"", connector_type);
+    }
+
+    format!("\"{} ({})\"", connector_type, count)
+}
+
+/// Scales the pen width of an edge logarithmically from 1 to 5 by its count relative to the
+/// largest count, so that hot paths stand out without hiding the others.
+pub(crate) fn get_edge_penwidth(count: usize, max_count: usize) -> f64 {
+    const MIN_PENWIDTH: f64 = 1.0;
+    const MAX_PENWIDTH: f64 = 5.0;
+
+    if count <= 1 || max_count <= 1 {
+        return MIN_PENWIDTH;
+    }
+
+    let ratio = (count as f64).ln() / (max_count as f64).ln();
+    MIN_PENWIDTH + (MAX_PENWIDTH - MIN_PENWIDTH) * ratio
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::test_support::{temp_path, values};
+
+    fn ci_json(id: &str) -> String {
+        format!(
+            "{{\"id\": \"{}\", \"connector_type\": \"HTTP\", \
+             \"source_component_values\": {{\"host\": \"a\"}}, \
+             \"target_component_values\": {{\"host\": \"b\"}}}}",
+            id
+        )
+    }
+
+    fn ci(id: &str, source: &[(&str, &str)], target: &[(&str, &str)]) -> Ci {
+        Ci {
+            id: String::from(id),
+            procedure: String::from("call"),
+            index: 0,
+            connector_type: String::from("HTTP"),
+            source_component_values: values(source),
+            source_context_values: HashMap::new(),
+            target_component_values: values(target),
+            target_context_values: HashMap::new(),
+            location: None,
+        }
+    }
+
+    fn build(cis: Vec<Ci>, provenance: bool) -> Model {
+        build_model(cis.into_iter().map(Ok), provenance).unwrap()
+    }
+
+    /// The ID of the component with the identifier values
+    fn id_of(model: &Model, pairs: &[(&str, &str)]) -> String {
+        let values: IdentifierValues = values(pairs);
+        model
+            .components
+            .iter()
+            .find(|component| component.component_values == values)
+            .map(|component| component.id.clone())
+            .unwrap()
+    }
+
+    fn read_ids(name: &str, content: &str) -> Vec<String> {
+        let path = temp_path(name);
+        fs::write(&path, content).unwrap();
+        let ids = read_cis(&path).unwrap().map(|ci| ci.unwrap().id).collect();
+        fs::remove_file(&path).unwrap();
+
+        ids
+    }
+
+    #[test]
+    fn reads_json_lines_and_json_arrays() {
+        assert_eq!(
+            read_ids(
+                "cis.jsonl",
+                &format!("{}\n\n{}\n", ci_json("r_p_1"), ci_json("r_p_2"))
+            ),
+            ["r_p_1", "r_p_2"]
+        );
+        assert_eq!(
+            read_ids(
+                "cis.json",
+                &format!("\n  [{},\n{}]", ci_json("r_p_1"), ci_json("r_p_2"))
+            ),
+            ["r_p_1", "r_p_2"]
+        );
+        assert!(read_ids("empty.jsonl", "\n").is_empty());
+    }
+
+    #[test]
+    fn derives_stable_component_ids() {
+        let host_and_port = values(&[("host", "a"), ("port", "80")]);
+        let id = get_component_id(&host_and_port, 0);
+        assert_eq!(id, "SDRPFWTBCQ");
+        assert_ne!(get_component_id(&host_and_port, 1), id);
+
+        // Values are hashed with their lengths, so they cannot be shifted between each other.
+        assert_ne!(
+            get_component_id(&values(&[("host", "ab")]), 0),
+            get_component_id(&values(&[("hosta", "b")]), 0)
+        );
+    }
+
+    #[test]
+    fn component_ids_do_not_depend_on_the_order_of_connector_instances() {
+        let cis = || {
+            vec![
+                ci(
+                    "r_call_1",
+                    &[("host", "a"), ("port", "80")],
+                    &[("host", "b")],
+                ),
+                ci("r_call_2", &[("host", "b")], &[("host", "c")]),
+                ci("r_call_3", &[("host", "a")], &[("host", "c")]),
+            ]
+        };
+        let model = build(cis(), false);
+        let mut reversed = cis();
+        reversed.reverse();
+        let reversed = build(reversed, false);
+
+        assert_eq!(model.components.len(), 3);
+        for host in ["a", "b", "c"] {
+            let pairs: &[(&str, &str)] = if host == "a" {
+                &[("host", "a"), ("port", "80")]
+            } else {
+                &[("host", host)]
+            };
+            assert_eq!(id_of(&model, pairs), id_of(&reversed, pairs));
+        }
+    }
+
+    #[test]
+    fn builds_byte_identical_models_from_shuffled_connector_instances() {
+        let cis = |order: &[usize]| -> Vec<Ci> {
+            let mut cis = vec![
+                ci(
+                    "r_call_1",
+                    &[("host", "a"), ("port", "80")],
+                    &[("host", "b")],
+                ),
+                ci(
+                    "r_call_2",
+                    &[("host", "a"), ("port", "81")],
+                    &[("host", "c")],
+                ),
+                ci("r_call_3", &[("host", "a")], &[("host", "b")]),
+                ci("r_call_4", &[("host", "b")], &[("host", "c")]),
+                ci("r_call_5", &[("host", "c")], &[("host", "b")]),
+                ci("r_call_6", &[("host", "b")], &[("host", "c")]),
+            ];
+            cis[3].connector_type = String::from("gRPC");
+            for (index, ci) in cis.iter_mut().enumerate() {
+                ci.source_context_values =
+                    HashMap::from([(String::from("pid"), format!("{}", index % 3))]);
+            }
+
+            let mut slots: Vec<Option<Ci>> = cis.into_iter().map(Some).collect();
+            order.iter().map(|i| slots[*i].take().unwrap()).collect()
+        };
+
+        for provenance in [false, true] {
+            let expected =
+                serde_json::to_string(&build(cis(&[0, 1, 2, 3, 4, 5]), provenance)).unwrap();
+            // The bridge of port 80 and 81 makes a conflict, which must be written too.
+            assert!(expected.contains("\"conflicts\""), "{}", expected);
+
+            for order in [
+                [5, 4, 3, 2, 1, 0],
+                [2, 0, 4, 1, 5, 3],
+                [3, 5, 1, 4, 0, 2],
+                [1, 3, 5, 0, 2, 4],
+            ] {
+                assert_eq!(
+                    serde_json::to_string(&build(cis(&order), provenance)).unwrap(),
+                    expected,
+                    "{:?}",
+                    order
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn counts_connector_instances_per_connector() {
+        let mut cis = vec![
+            ci("r_get_1", &[("host", "a")], &[("host", "b")]),
+            ci("r_get_2", &[("host", "a")], &[("host", "b")]),
+            ci("r_put_1", &[("host", "a")], &[("host", "b")]),
+            ci("r_get_3", &[("host", "b")], &[("host", "a")]),
+        ];
+        cis[0].procedure = String::from("get");
+        cis[1].procedure = String::from("get");
+        cis[2].procedure = String::from("put");
+        cis[3].procedure = String::new();
+
+        let model = build(cis, true);
+        let (a, b) = (
+            id_of(&model, &[("host", "a")]),
+            id_of(&model, &[("host", "b")]),
+        );
+        let forward = model
+            .connectors
+            .iter()
+            .find(|connector| connector.source_component_id == a)
+            .unwrap();
+        let backward = model
+            .connectors
+            .iter()
+            .find(|connector| connector.source_component_id == b)
+            .unwrap();
+        assert_eq!(model.connectors.len(), 2);
+        assert_eq!((forward.count, backward.count), (3, 1));
+        assert_eq!(
+            forward.procedures,
+            BTreeSet::from([String::from("get"), String::from("put")])
+        );
+        assert!(backward.procedures.is_empty());
+        assert_eq!(
+            forward.trace_ids,
+            BTreeSet::from([
+                String::from("r_get_1"),
+                String::from("r_get_2"),
+                String::from("r_put_1")
+            ])
+        );
+
+        assert_eq!(forward.trace_id_sample, forward.trace_ids);
+
+        // Without provenance, only the trace ID sample is recorded.
+        let model = build(
+            vec![ci("r_get_1", &[("host", "a")], &[("host", "b")])],
+            false,
+        );
+        assert_eq!(model.connectors[0].count, 1);
+        assert_eq!(
+            model.connectors[0].trace_id_sample,
+            BTreeSet::from([String::from("r_get_1")])
+        );
+        assert!(model.connectors[0].trace_ids.is_empty());
+        assert!(model.connectors[0].evidence.is_empty());
+    }
+
+    #[test]
+    fn keeps_models_without_provenance_bounded() {
+        let cis = |len: usize| {
+            (0..len)
+                .rev()
+                .map(|i| {
+                    ci(
+                        &format!("r_call_{:05}", i),
+                        &[("host", "a")],
+                        &[("host", "b")],
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let small = build(cis(10), false);
+        let large = build(cis(10_000), false);
+        assert_eq!(large.connectors[0].count, 10_000);
+        assert_eq!(
+            large.connectors[0].trace_id_sample,
+            (0..TRACE_ID_SAMPLE_SIZE)
+                .map(|i| format!("r_call_{:05}", i))
+                .collect::<BTreeSet<_>>()
+        );
+        assert!(large.connectors[0].trace_ids.is_empty());
+
+        // Only the count differs, so the model does not grow with the connector instances.
+        let json = |model: &Model| serde_json::to_string(model).unwrap();
+        assert_eq!(json(&small).len() + 3, json(&large).len());
+
+        assert_eq!(
+            build(cis(10_000), true).connectors[0].trace_ids.len(),
+            10_000
+        );
+    }
+
+    #[test]
+    fn records_evidence_only_in_the_provenance_mode() {
+        let cis = || {
+            let mut cis = vec![
+                ci("r_call_1", &[("host", "a")], &[("host", "b")]),
+                ci("r_call_2", &[("host", "b")], &[("host", "c")]),
+            ];
+            cis[0].location = Some(TraceLocation {
+                file: String::from("traces.jsonl"),
+                line: 7,
+            });
+            cis
+        };
+
+        let model = build(cis(), true);
+        let evidence_of = |pairs: &[(&str, &str)]| -> Vec<String> {
+            let id = id_of(&model, pairs);
+            let component = model.components.iter().find(|c| c.id == id).unwrap();
+            component.evidence.iter().map(|e| e.to_string()).collect()
+        };
+        assert_eq!(
+            evidence_of(&[("host", "a")]),
+            ["r_call_1 at traces.jsonl:7"]
+        );
+        assert_eq!(
+            evidence_of(&[("host", "b")]),
+            ["r_call_1 at traces.jsonl:7", "r_call_2"]
+        );
+        assert!(model
+            .connectors
+            .iter()
+            .all(|connector| connector.evidence.len() == 1));
+
+        let model = build(cis(), false);
+        assert!(model.components.iter().all(|c| c.evidence.is_empty()));
+        assert!(model.connectors.iter().all(|c| c.evidence.is_empty()));
+    }
+
+    #[test]
+    fn merges_connector_instances_into_a_base_model() {
+        let base = build(
+            vec![
+                ci("r_call_1", &[("host", "a")], &[("host", "b")]),
+                ci("r_call_2", &[("host", "x")], &[("ip", "10.0.0.1")]),
+            ],
+            false,
+        );
+        let a = id_of(&base, &[("host", "a")]);
+        let b = id_of(&base, &[("host", "b")]);
+        let mut xy = vec![
+            id_of(&base, &[("host", "x")]),
+            id_of(&base, &[("ip", "10.0.0.1")]),
+        ];
+        xy.sort();
+
+        let (model, report) = merge_model(
+            base,
+            vec![
+                ci("r_call_3", &[("host", "a")], &[("host", "b")]),
+                ci(
+                    "r_call_4",
+                    &[("host", "b"), ("port", "80")],
+                    &[("host", "c")],
+                ),
+                // Both base components are subsets of the source, so they are one component.
+                ci(
+                    "r_call_5",
+                    &[("host", "x"), ("ip", "10.0.0.1")],
+                    &[("host", "a")],
+                ),
+            ]
+            .into_iter()
+            .map(Ok),
+            false,
+        )
+        .unwrap();
+
+        // Base components keep their IDs, and merged components keep the first one.
+        assert_eq!(id_of(&model, &[("host", "a")]), a);
+        assert_eq!(id_of(&model, &[("host", "b"), ("port", "80")]), b);
+        assert_eq!(id_of(&model, &[("host", "x"), ("ip", "10.0.0.1")]), xy[0]);
+        let c = id_of(&model, &[("host", "c")]);
+        assert_eq!(model.components.len(), 4);
+
+        assert_eq!(report.added_components, [c]);
+        let mut extended = vec![b, xy[0].clone()];
+        extended.sort();
+        assert_eq!(report.extended_components, extended);
+        assert_eq!(report.untouched_components, [a.as_str()]);
+        assert_eq!(report.merged_components, [xy.clone()]);
+
+        // a -> b is extended, b -> c and xy -> a are added, and x -> y becomes an untouched
+        // self-loop of the merged component.
+        assert_eq!(report.added_connectors, 2);
+        assert_eq!(report.extended_connectors, 1);
+        assert_eq!(report.untouched_connectors, 1);
+        let count_of = |source: &str, target: &str| {
+            model
+                .connectors
+                .iter()
+                .find(|c| c.source_component_id == source && c.target_component_id == target)
+                .map(|c| c.count)
+        };
+        assert_eq!(
+            count_of(&a, &id_of(&model, &[("host", "b"), ("port", "80")])),
+            Some(2)
+        );
+        assert_eq!(count_of(&xy[0], &xy[0]), Some(1));
+    }
+
+    #[test]
+    fn groups_components_by_nested_keys() {
+        let model: Model = serde_json::from_str(
+            r#"{
+                "components": [
+                    {"id": "h1", "component_values": {"host": "h1"}},
+                    {"id": "h1p1", "component_values": {"host": "h1", "pid": "1"}},
+                    {"id": "h1p2", "component_values": {"host": "h1", "pid": "2"}},
+                    {"id": "h2p1", "component_values": {"host": "h2", "pid": "1"}},
+                    {"id": "db", "component_values": {"db": "users"}}
+                ],
+                "connectors": []
+            }"#,
+        )
+        .unwrap();
+        let groups = group_components(
+            &model.components,
+            &[String::from("host"), String::from("pid")],
+        );
+
+        fn outline(groups: &[Group], indent: &str) -> String {
+            groups
+                .iter()
+                .map(|group| {
+                    format!(
+                        "{}{}:{} {:?}\n{}",
+                        indent,
+                        group.key,
+                        group.value,
+                        group.component_ids,
+                        outline(&group.groups, &format!("{}  ", indent))
+                    )
+                })
+                .collect()
+        }
+
+        // The component without a host is in no group, and the one without a pid stays in its
+        // host group.
+        assert_eq!(
+            outline(&groups, ""),
+            "host:h1 [\"h1\"]\n  \
+             pid:1 [\"h1p1\"]\n  \
+             pid:2 [\"h1p2\"]\n\
+             host:h2 []\n  \
+             pid:1 [\"h2p1\"]\n"
+        );
+    }
+
+    #[test]
+    fn draws_groups_as_nested_dot_clusters() {
+        let mut model = build(
+            vec![ci(
+                "r_call_1",
+                &[("host", "h1"), ("pid", "1")],
+                &[("db", "users")],
+            )],
+            false,
+        );
+        model.groups = group_components(
+            &model.components,
+            &[String::from("host"), String::from("pid")],
+        );
+        let dot = get_dot_graph(model).print(&mut PrinterContext::default());
+
+        let outer = dot.find("subgraph cluster_0 {").unwrap();
+        let inner = dot.find("subgraph cluster_0_0 {").unwrap();
+        assert!(outer < inner);
+        assert!(dot.contains("label=\"host:h1\""));
+        assert!(dot.contains("label=\"pid:1\""));
+        assert!(!dot.contains("cluster_1"));
+    }
 }
@@ -0,0 +1,801 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    error::Error,
+    fmt::{self, Display, Formatter},
+};
+
+use graphviz_rust::printer::PrinterContext;
+use graphviz_rust::{dot_generator::*, dot_structures::*, printer::DotPrinter};
+use serde::Serialize;
+
+use crate::{
+    conn::{self, Model},
+    identity::{IdentifierValues, IdentityResolver},
+    label::LabelRules,
+};
+
+#[derive(Debug)]
+pub enum DiffError {
+    UnknownComponent(String),
+}
+
+impl Error for DiffError {}
+
+impl Display for DiffError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            DiffError::UnknownComponent(id) => {
+                write!(f, "A connector refers to an unknown component: {}", id)
+            }
+        }
+    }
+}
+
+const ADDED_COLOR: &str = "green";
+const REMOVED_COLOR: &str = "red";
+const CHANGED_COLOR: &str = "orange";
+
+/// A component of either model
+#[derive(Debug, Serialize)]
+pub struct DiffComponent {
+    pub id: String,
+    pub component_values: IdentifierValues,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+}
+
+/// A component of both models whose identifier values differ, or whose context values differ if
+/// they are compared
+#[derive(Debug, Serialize)]
+pub struct ChangedComponent {
+    pub old_id: String,
+    pub new_id: String,
+    pub old_component_values: IdentifierValues,
+    pub new_component_values: IdentifierValues,
+
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub old_context_values: ContextValues,
+
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub new_context_values: ContextValues,
+
+    /// The label of the component by its new identifier values
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+}
+
+/// A connector of either model. Component IDs are those of the model that has the connector.
+#[derive(Debug, Serialize)]
+pub struct DiffConnector {
+    pub connector_type: String,
+    pub source_component_id: String,
+    pub target_component_id: String,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ModelDiff {
+    pub added_components: Vec<DiffComponent>,
+    pub removed_components: Vec<DiffComponent>,
+    pub changed_components: Vec<ChangedComponent>,
+    pub added_connectors: Vec<DiffConnector>,
+    pub removed_connectors: Vec<DiffConnector>,
+
+    /// Every component of both models, which the DOT overlay is drawn from
+    #[serde(skip)]
+    overlay_components: Vec<OverlayComponent>,
+
+    /// Every connector of both models with IDs of `overlay_components`
+    #[serde(skip)]
+    overlay_connectors: Vec<(String, String, String, Option<&'static str>)>,
+}
+
+type ContextValues = BTreeMap<String, BTreeSet<String>>;
+
+/// Indexes of the connectors of a model by their connector types and the identities of their
+/// components
+type ConnectorKeys = BTreeMap<(String, usize, usize), Vec<usize>>;
+
+/// A component in the DOT overlay with the ID of the new model if it has one
+#[derive(Debug)]
+struct OverlayComponent {
+    id: String,
+    values: IdentifierValues,
+    label: Option<String>,
+    status: Option<&'static str>,
+}
+
+/// The components of one resolved identity in the old and the new model
+#[derive(Default)]
+struct Matched<'a> {
+    old: Vec<&'a conn::Component>,
+    new: Vec<&'a conn::Component>,
+}
+
+/// Compares two models. Component IDs depend on identifier values that may change between the
+/// models, so components are matched by resolving the identifier values of both models together
+/// with `IdentityResolver`, as if they had been observed in one run. Components that keep their
+/// IDs are also matched, even if their identifier values have changed completely, e.g., in models
+/// merged with a base model. One identity may have several components in a model, e.g., if a
+/// component has been split by a new identifier, so the components of an identity are paired and
+/// compared one by one, and those left over are reported as added or removed. Connectors are
+/// matched by their connector types and the identities of their components, and a connector to
+/// a component that its model does not have is an error. Components are labeled by label rules if
+/// they are given.
+///
+/// Context values are metadata that vary between runs, e.g., process IDs, so they are only
+/// compared if `context` is set.
+pub fn diff_models(
+    old: &Model,
+    new: &Model,
+    labels: Option<&LabelRules>,
+    context: bool,
+) -> Result<ModelDiff, DiffError> {
+    let mut resolver = IdentityResolver::default();
+    let old_value_sets: Vec<usize> = old
+        .components
+        .iter()
+        .map(|c| resolver.add(c.component_values.clone()))
+        .collect();
+    let new_value_sets: Vec<usize> = new
+        .components
+        .iter()
+        .map(|c| resolver.add(c.component_values.clone()))
+        .collect();
+    let identities = resolver.resolve();
+
+    let mut matched: Vec<Matched> = (0..identities.identities.len())
+        .map(|_| Matched::default())
+        .collect();
+    for (component, value_set) in old.components.iter().zip(old_value_sets) {
+        let identity = identities.identity_of(value_set);
+        matched[identity].old.push(component);
+    }
+    for (component, value_set) in new.components.iter().zip(new_value_sets) {
+        let identity = identities.identity_of(value_set);
+        matched[identity].new.push(component);
+    }
+    match_by_ids(&mut matched);
+
+    let mut identity_of_id: BTreeMap<(bool, &str), usize> = BTreeMap::new();
+    for (identity, m) in matched.iter().enumerate() {
+        for component in &m.old {
+            identity_of_id.insert((false, &component.id), identity);
+        }
+        for component in &m.new {
+            identity_of_id.insert((true, &component.id), identity);
+        }
+    }
+
+    let mut diff = ModelDiff::default();
+    // The overlay ID of each component, which is the ID of its new counterpart if it has one
+    let mut overlay_id_of: HashMap<(bool, &str), String> = HashMap::new();
+    let label_of = |values: &IdentifierValues| labels.and_then(|labels| labels.label(values));
+    for m in &matched {
+        let paired = pair_components(m);
+
+        for (old_component, new_component) in paired.pairs {
+            let (old_context_values, new_context_values) = if context {
+                (
+                    old_component.context_values.clone(),
+                    new_component.context_values.clone(),
+                )
+            } else {
+                (ContextValues::new(), ContextValues::new())
+            };
+
+            let status = if old_component.component_values != new_component.component_values
+                || old_context_values != new_context_values
+            {
+                diff.changed_components.push(ChangedComponent {
+                    old_id: old_component.id.clone(),
+                    new_id: new_component.id.clone(),
+                    old_component_values: old_component.component_values.clone(),
+                    new_component_values: new_component.component_values.clone(),
+                    old_context_values,
+                    new_context_values,
+                    label: label_of(&new_component.component_values),
+                });
+                Some(CHANGED_COLOR)
+            } else {
+                None
+            };
+
+            overlay_id_of.insert((false, &old_component.id), new_component.id.clone());
+            overlay_id_of.insert((true, &new_component.id), new_component.id.clone());
+            diff.overlay_components
+                .push(to_overlay_component(new_component, status, labels));
+        }
+
+        for new_component in paired.new_only {
+            diff.added_components
+                .push(to_diff_component(new_component, labels));
+            overlay_id_of.insert((true, &new_component.id), new_component.id.clone());
+            diff.overlay_components.push(to_overlay_component(
+                new_component,
+                Some(ADDED_COLOR),
+                labels,
+            ));
+        }
+
+        for old_component in paired.old_only {
+            diff.removed_components
+                .push(to_diff_component(old_component, labels));
+            overlay_id_of.insert((false, &old_component.id), old_component.id.clone());
+            diff.overlay_components.push(to_overlay_component(
+                old_component,
+                Some(REMOVED_COLOR),
+                labels,
+            ));
+        }
+    }
+
+    // Connectors are matched by their connector types and the identities of their components, so
+    // one key may have several connectors, e.g., to components that have been split.
+    // A connector to an unknown component would otherwise be left out of the diff.
+    let connector_keys = |model: &Model, is_new: bool| -> Result<ConnectorKeys, DiffError> {
+        let identity = |id: &str| {
+            identity_of_id
+                .get(&(is_new, id))
+                .copied()
+                .ok_or_else(|| DiffError::UnknownComponent(id.to_string()))
+        };
+        let mut keys = ConnectorKeys::new();
+        for (index, c) in model.connectors.iter().enumerate() {
+            let source = identity(&c.source_component_id)?;
+            let target = identity(&c.target_component_id)?;
+            keys.entry((c.connector_type.clone(), source, target))
+                .or_default()
+                .push(index);
+        }
+        Ok(keys)
+    };
+    let old_keys = connector_keys(old, false)?;
+    let new_keys = connector_keys(new, true)?;
+
+    let mut overlay_connectors: BTreeSet<(String, String, String, Option<&'static str>)> =
+        BTreeSet::new();
+    let mut add_overlay_connector =
+        |connector: &conn::Connector, is_new: bool, status: Option<&'static str>| {
+            overlay_connectors.insert((
+                connector.connector_type.clone(),
+                overlay_id_of[&(is_new, connector.source_component_id.as_str())].clone(),
+                overlay_id_of[&(is_new, connector.target_component_id.as_str())].clone(),
+                status,
+            ));
+        };
+    for (key, indexes) in &new_keys {
+        let status = if old_keys.contains_key(key) {
+            None
+        } else {
+            Some(ADDED_COLOR)
+        };
+        for index in indexes {
+            let connector = &new.connectors[*index];
+            if status.is_some() {
+                diff.added_connectors.push(to_diff_connector(connector));
+            }
+            add_overlay_connector(connector, true, status);
+        }
+    }
+    for (key, indexes) in &old_keys {
+        if new_keys.contains_key(key) {
+            continue;
+        }
+        for index in indexes {
+            let connector = &old.connectors[*index];
+            diff.removed_connectors.push(to_diff_connector(connector));
+            add_overlay_connector(connector, false, Some(REMOVED_COLOR));
+        }
+    }
+    diff.overlay_connectors = overlay_connectors.into_iter().collect();
+
+    Ok(diff)
+}
+
+/// The old and new components of an identity, paired so that every component is reported
+struct Paired<'a> {
+    pairs: Vec<(&'a conn::Component, &'a conn::Component)>,
+
+    /// Old components left over, e.g., because they have been merged in the new model
+    old_only: Vec<&'a conn::Component>,
+
+    /// New components left over, e.g., because an old component has been split into them
+    new_only: Vec<&'a conn::Component>,
+}
+
+/// Pairs the old and new components of an identity. Components that keep their IDs are paired
+/// first, and the others in order.
+fn pair_components<'a>(m: &Matched<'a>) -> Paired<'a> {
+    let mut pairs: Vec<(&conn::Component, &conn::Component)> = Vec::new();
+    let mut old_rest: Vec<&conn::Component> = Vec::new();
+    let mut new_rest: Vec<&conn::Component> = m.new.clone();
+    for old_component in &m.old {
+        match new_rest.iter().position(|c| c.id == old_component.id) {
+            Some(position) => pairs.push((old_component, new_rest.remove(position))),
+            None => old_rest.push(old_component),
+        }
+    }
+
+    let paired = old_rest.len().min(new_rest.len());
+    let old_only = old_rest.split_off(paired);
+    let new_only = new_rest.split_off(paired);
+    pairs.extend(old_rest.into_iter().zip(new_rest));
+
+    Paired {
+        pairs,
+        old_only,
+        new_only,
+    }
+}
+
+/// Matches components of identities that are only in the old model to identities that are only
+/// in the new model by their IDs. The old components are moved to the new identity.
+fn match_by_ids(matched: &mut [Matched]) {
+    let new_only: HashMap<&str, usize> = matched
+        .iter()
+        .enumerate()
+        .filter(|(_, m)| m.old.is_empty())
+        .flat_map(|(identity, m)| m.new.iter().map(move |c| (c.id.as_str(), identity)))
+        .collect();
+
+    for identity in 0..matched.len() {
+        if !matched[identity].new.is_empty() {
+            continue;
+        }
+
+        let new_identity = matched[identity]
+            .old
+            .iter()
+            .find_map(|c| new_only.get(c.id.as_str()).copied());
+        if let Some(new_identity) = new_identity {
+            let old = std::mem::take(&mut matched[identity].old);
+            matched[new_identity].old.extend(old);
+        }
+    }
+}
+
+fn to_diff_component(component: &conn::Component, labels: Option<&LabelRules>) -> DiffComponent {
+    DiffComponent {
+        id: component.id.clone(),
+        component_values: component.component_values.clone(),
+        label: labels.and_then(|labels| labels.label(&component.component_values)),
+    }
+}
+
+fn to_overlay_component(
+    component: &conn::Component,
+    status: Option<&'static str>,
+    labels: Option<&LabelRules>,
+) -> OverlayComponent {
+    OverlayComponent {
+        id: component.id.clone(),
+        values: component.component_values.clone(),
+        label: labels.and_then(|labels| labels.label(&component.component_values)),
+        status,
+    }
+}
+
+fn to_diff_connector(connector: &conn::Connector) -> DiffConnector {
+    DiffConnector {
+        connector_type: connector.connector_type.clone(),
+        source_component_id: connector.source_component_id.clone(),
+        target_component_id: connector.target_component_id.clone(),
+    }
+}
+
+/// Writes a model diff as "text", "json", or "dot". The DOT overlay draws both models in one graph,
+/// in which added elements are green, removed elements are red, and changed components are
+/// orange.
+pub fn write_diff(
+    diff: &ModelDiff,
+    output_file_path_str: &str,
+    output_format_str: &str,
+) -> Result<(), Box<dyn Error>> {
+    let result = match output_format_str {
+        "json" => serde_json::to_string_pretty(diff)?,
+        "dot" => get_dot_overlay(diff).print(&mut PrinterContext::default()),
+        _ => get_text(diff),
+    };
+
+    conn::print_result_str(&result, output_file_path_str)
+}
+
+fn get_text(diff: &ModelDiff) -> String {
+    let mut s = String::new();
+    s.push_str(&format!(
+        "components: {} added, {} removed, {} changed\n",
+        diff.added_components.len(),
+        diff.removed_components.len(),
+        diff.changed_components.len()
+    ));
+    s.push_str(&format!(
+        "connectors: {} added, {} removed\n",
+        diff.added_connectors.len(),
+        diff.removed_connectors.len()
+    ));
+
+    for component in &diff.added_components {
+        s.push_str(&format!(
+            "+ component {}{} ({})\n",
+            component.id,
+            format_label(&component.label),
+            conn::format_values(&component.component_values)
+        ));
+    }
+    for component in &diff.removed_components {
+        s.push_str(&format!(
+            "- component {}{} ({})\n",
+            component.id,
+            format_label(&component.label),
+            conn::format_values(&component.component_values)
+        ));
+    }
+    for component in &diff.changed_components {
+        s.push_str(&format!(
+            "~ component {} -> {}{} ({} -> {})\n",
+            component.old_id,
+            component.new_id,
+            format_label(&component.label),
+            conn::format_values(&component.old_component_values),
+            conn::format_values(&component.new_component_values)
+        ));
+        if component.old_context_values != component.new_context_values {
+            s.push_str(&format!(
+                "    context: {} -> {}\n",
+                format_context_values(&component.old_context_values),
+                format_context_values(&component.new_context_values)
+            ));
+        }
+    }
+    for (sign, connectors) in [
+        ("+", &diff.added_connectors),
+        ("-", &diff.removed_connectors),
+    ] {
+        for connector in connectors {
+            s.push_str(&format!(
+                "{} connector {} -> {} [{}]\n",
+                sign,
+                connector.source_component_id,
+                connector.target_component_id,
+                connector.connector_type
+            ));
+        }
+    }
+
+    s
+}
+
+/// Formats context values as "key:value|value, ..." for the text output.
+fn format_context_values(values: &ContextValues) -> String {
+    values
+        .iter()
+        .map(|(key, values)| {
+            let values: Vec<&str> = values.iter().map(|v| v.as_str()).collect();
+            format!("{}:{}", key, values.join("|"))
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Formats a label after a component ID in the text output.
+fn format_label(label: &Option<String>) -> String {
+    match label {
+        Some(label) => format!(" \"{}\"", label),
+        None => String::new(),
+    }
+}
+
+fn get_dot_overlay(diff: &ModelDiff) -> Graph {
+    let mut g = graph!(di id!("diff"));
+
+    for component in &diff.overlay_components {
+        let label = match &component.label {
+            Some(label) => conn::quote_dot(label),
+            None => conn::get_node_label(&component.values),
+        };
+        let mut node = node!(component.id;attr!("label", &label));
+        if let Some(color) = component.status {
+            node.attributes.push(attr!("color", color));
+            node.attributes.push(attr!("fontcolor", color));
+        }
+        g.add_stmt(stmt!(node));
+    }
+
+    for (connector_type, source, target, status) in &diff.overlay_connectors {
+        let label = conn::get_edge_label(connector_type, 0);
+        let mut edge = edge!(node_id!(source) => node_id!(target);attr!("label", &label));
+        if let Some(color) = status {
+            edge.attributes.push(attr!("color", color));
+            edge.attributes.push(attr!("fontcolor", color));
+        }
+        g.add_stmt(stmt!(edge));
+    }
+
+    g
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::{label, test_support::temp_path};
+
+    fn model(json: &str) -> Model {
+        serde_json::from_str(json).unwrap()
+    }
+
+    fn ids(components: &[DiffComponent]) -> Vec<&str> {
+        components.iter().map(|c| c.id.as_str()).collect()
+    }
+
+    fn connectors(connectors: &[DiffConnector]) -> Vec<(&str, &str, &str)> {
+        connectors
+            .iter()
+            .map(|c| {
+                (
+                    c.connector_type.as_str(),
+                    c.source_component_id.as_str(),
+                    c.target_component_id.as_str(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn reports_added_and_removed_components_and_connectors() {
+        let old = model(
+            r#"{
+                "components": [
+                    {"id": "a", "component_values": {"host": "a"}},
+                    {"id": "b", "component_values": {"host": "b"}}
+                ],
+                "connectors": [
+                    {"connector_type": "HTTP", "source_component_id": "a", "target_component_id": "b"}
+                ]
+            }"#,
+        );
+        let new = model(
+            r#"{
+                "components": [
+                    {"id": "a", "component_values": {"host": "a"}},
+                    {"id": "c", "component_values": {"host": "c"}}
+                ],
+                "connectors": [
+                    {"connector_type": "HTTP", "source_component_id": "a", "target_component_id": "c"}
+                ]
+            }"#,
+        );
+
+        let diff = diff_models(&old, &new, None, false).unwrap();
+        assert_eq!(ids(&diff.added_components), ["c"]);
+        assert_eq!(ids(&diff.removed_components), ["b"]);
+        assert!(diff.changed_components.is_empty());
+        assert_eq!(connectors(&diff.added_connectors), [("HTTP", "a", "c")]);
+        assert_eq!(connectors(&diff.removed_connectors), [("HTTP", "a", "b")]);
+    }
+
+    #[test]
+    fn rejects_connectors_to_unknown_components() {
+        let old = model(
+            r#"{
+                "components": [{"id": "a", "component_values": {"host": "a"}}],
+                "connectors": []
+            }"#,
+        );
+        let new = model(
+            r#"{
+                "components": [{"id": "a", "component_values": {"host": "a"}}],
+                "connectors": [
+                    {"connector_type": "HTTP", "source_component_id": "a", "target_component_id": "x"}
+                ]
+            }"#,
+        );
+
+        for (old, new) in [(&old, &new), (&new, &old)] {
+            let error = diff_models(old, new, None, false).unwrap_err();
+            assert!(matches!(error, DiffError::UnknownComponent(id) if id == "x"));
+        }
+    }
+
+    #[test]
+    fn matches_components_by_identifier_values_across_ids() {
+        let old = model(
+            r#"{
+                "components": [
+                    {"id": "old-a", "component_values": {"host": "a"}},
+                    {"id": "old-b", "component_values": {"host": "b", "port": "80"}}
+                ],
+                "connectors": [
+                    {"connector_type": "HTTP", "source_component_id": "old-a", "target_component_id": "old-b"}
+                ]
+            }"#,
+        );
+        let new = model(
+            r#"{
+                "components": [
+                    {"id": "new-a", "component_values": {"host": "a", "port": "8080"}},
+                    {"id": "new-b", "component_values": {"host": "b", "port": "80"}}
+                ],
+                "connectors": [
+                    {"connector_type": "HTTP", "source_component_id": "new-a", "target_component_id": "new-b"}
+                ]
+            }"#,
+        );
+
+        let diff = diff_models(&old, &new, None, false).unwrap();
+        assert!(diff.added_components.is_empty());
+        assert!(diff.removed_components.is_empty());
+        assert!(diff.added_connectors.is_empty());
+        assert!(diff.removed_connectors.is_empty());
+
+        // Only the component whose identifier values have grown is changed.
+        let [changed] = diff.changed_components.as_slice() else {
+            panic!("{:?}", diff.changed_components);
+        };
+        assert_eq!(
+            (changed.old_id.as_str(), changed.new_id.as_str()),
+            ("old-a", "new-a")
+        );
+        assert_eq!(changed.old_component_values.len(), 1);
+        assert_eq!(changed.new_component_values.len(), 2);
+    }
+
+    #[test]
+    fn reports_every_component_of_a_split_identity() {
+        let old = model(
+            r#"{
+                "components": [
+                    {"id": "x", "component_values": {"host": "x"}},
+                    {"id": "a", "component_values": {"host": "a"}}
+                ],
+                "connectors": [
+                    {"connector_type": "HTTP", "source_component_id": "x", "target_component_id": "a"}
+                ]
+            }"#,
+        );
+        let new = model(
+            r#"{
+                "components": [
+                    {"id": "x", "component_values": {"host": "x"}},
+                    {"id": "a80", "component_values": {"host": "a", "port": "80"}},
+                    {"id": "a81", "component_values": {"host": "a", "port": "81"}}
+                ],
+                "connectors": [
+                    {"connector_type": "HTTP", "source_component_id": "x", "target_component_id": "a80"},
+                    {"connector_type": "HTTP", "source_component_id": "x", "target_component_id": "a81"}
+                ]
+            }"#,
+        );
+
+        // The old component is paired with one of the new components, and the other is added.
+        let diff = diff_models(&old, &new, None, false).unwrap();
+        let [changed] = diff.changed_components.as_slice() else {
+            panic!("{:?}", diff.changed_components);
+        };
+        assert_eq!(
+            (changed.old_id.as_str(), changed.new_id.as_str()),
+            ("a", "a80")
+        );
+        assert_eq!(changed.new_component_values.get("port").unwrap(), "80");
+        assert_eq!(ids(&diff.added_components), ["a81"]);
+        assert_eq!(
+            diff.added_components[0]
+                .component_values
+                .get("port")
+                .unwrap(),
+            "81"
+        );
+        assert!(diff.removed_components.is_empty());
+
+        // Connectors to both components match the old connector.
+        assert!(diff.added_connectors.is_empty());
+        assert!(diff.removed_connectors.is_empty());
+        assert_eq!(diff.overlay_components.len(), 3);
+        assert_eq!(diff.overlay_connectors.len(), 2);
+
+        // Merging the components back reports the component left over as removed.
+        let diff = diff_models(&new, &old, None, false).unwrap();
+        assert_eq!(diff.changed_components.len(), 1);
+        assert_eq!(diff.changed_components[0].old_id, "a80");
+        assert_eq!(ids(&diff.removed_components), ["a81"]);
+        assert!(diff.added_components.is_empty());
+    }
+
+    #[test]
+    fn matches_components_that_keep_their_ids() {
+        let old = model(
+            r#"{"components": [{"id": "x", "component_values": {"host": "a"}}], "connectors": []}"#,
+        );
+        let new = model(
+            r#"{"components": [{"id": "x", "component_values": {"ip": "10.0.0.1"}}], "connectors": []}"#,
+        );
+
+        let diff = diff_models(&old, &new, None, false).unwrap();
+        assert!(diff.added_components.is_empty());
+        assert!(diff.removed_components.is_empty());
+        assert_eq!(diff.changed_components.len(), 1);
+        assert_eq!(diff.changed_components[0].old_id, "x");
+        assert_eq!(diff.changed_components[0].new_id, "x");
+    }
+
+    #[test]
+    fn reports_context_value_changes_only_if_compared() {
+        let old = model(
+            r#"{"components": [{"id": "a", "component_values": {"host": "a"}, "context_values": {"pid": ["1"]}}], "connectors": []}"#,
+        );
+        let new = model(
+            r#"{"components": [{"id": "a", "component_values": {"host": "a"}, "context_values": {"pid": ["1", "2"]}}], "connectors": []}"#,
+        );
+
+        // Context values vary between runs, so they are not compared by default.
+        let diff = diff_models(&old, &new, None, false).unwrap();
+        assert!(diff.changed_components.is_empty());
+        assert!(diff.overlay_components[0].status.is_none());
+
+        let diff = diff_models(&old, &new, None, true).unwrap();
+        assert_eq!(diff.changed_components.len(), 1);
+        assert_eq!(
+            get_text(&diff),
+            "components: 0 added, 0 removed, 1 changed\n\
+             connectors: 0 added, 0 removed\n\
+             ~ component a -> a (host:a -> host:a)\n    \
+             context: pid:1 -> pid:1|2\n"
+        );
+
+        assert!(diff_models(&old, &old, None, true)
+            .unwrap()
+            .changed_components
+            .is_empty());
+    }
+
+    #[test]
+    fn labels_text_and_colors_dot_overlay() {
+        let path = temp_path("labels.yaml");
+        fs::write(
+            &path,
+            "version: 1\ntemplates:\n- template: \"svc {host}\"\n",
+        )
+        .unwrap();
+        let labels = label::read_labels(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let old = model(
+            r#"{"components": [{"id": "a", "component_values": {"host": "a"}}], "connectors": []}"#,
+        );
+        let new = model(
+            r#"{
+                "components": [
+                    {"id": "a", "component_values": {"host": "a"}},
+                    {"id": "b", "component_values": {"host": "b"}}
+                ],
+                "connectors": [
+                    {"connector_type": "HTTP", "source_component_id": "a", "target_component_id": "b"}
+                ]
+            }"#,
+        );
+
+        let diff = diff_models(&old, &new, Some(&labels), false).unwrap();
+        assert_eq!(
+            get_text(&diff),
+            "components: 1 added, 0 removed, 0 changed\n\
+             connectors: 1 added, 0 removed\n\
+             + component b \"svc b\" (host:b)\n\
+             + connector a -> b [HTTP]\n"
+        );
+
+        let dot = get_dot_overlay(&diff).print(&mut PrinterContext::default());
+        assert!(dot.contains("a[label=\"svc a\"]"), "{}", dot);
+        assert!(
+            dot.contains("b[label=\"svc b\",color=green,fontcolor=green]"),
+            "{}",
+            dot
+        );
+        assert!(
+            dot.contains("a -> b [label=\"HTTP\",color=green,fontcolor=green]"),
+            "{}",
+            dot
+        );
+    }
+}
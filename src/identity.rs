@@ -0,0 +1,337 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+/// Identifier values of a component occurrence, e.g., the source of a connector instance.
+pub type IdentifierValues = BTreeMap<String, String>;
+
+/// Resolves which component occurrences belong to the same component.
+///
+/// Two occurrences belong to the same component if the identifier values of one are a subset of
+/// the identifier values of the other, and the relation is closed transitively with union-find.
+/// The result therefore does not depend on the order in which occurrences are added, and two
+/// components are unified when a later occurrence bridges them. Occurrences without identifier
+/// values only belong to each other.
+#[derive(Debug, Default)]
+pub struct IdentityResolver {
+    value_sets: Vec<IdentifierValues>,
+    indexes: HashMap<IdentifierValues, usize>,
+    links: Vec<(usize, usize)>,
+}
+
+/// A component that the resolver found.
+#[derive(Debug)]
+pub struct Identity {
+    /// Every identifier value of the occurrences. Conflicting identifiers keep the smallest value.
+    pub values: IdentifierValues,
+
+    /// Identifiers for which the occurrences have more than one value, with all of the values
+    pub conflicts: BTreeMap<String, BTreeSet<String>>,
+}
+
+/// Resolved components, ordered by their identifier values.
+#[derive(Debug)]
+pub struct Identities {
+    pub identities: Vec<Identity>,
+
+    /// The index of the identity of each value set in the order they were added
+    identity_of: Vec<usize>,
+}
+
+impl Identities {
+    pub fn identity_of(&self, value_set: usize) -> usize {
+        self.identity_of[value_set]
+    }
+}
+
+impl IdentityResolver {
+    /// Adds an occurrence and returns the index of its value set. Occurrences with the same
+    /// identifier values share a value set.
+    pub fn add(&mut self, values: IdentifierValues) -> usize {
+        if let Some(index) = self.indexes.get(&values) {
+            return *index;
+        }
+
+        let index = self.value_sets.len();
+        self.value_sets.push(values.clone());
+        self.indexes.insert(values, index);
+
+        index
+    }
+
+    /// Declares that two value sets belong to the same component even if neither is a subset of
+    /// the other, e.g., because an existing model has already resolved them.
+    pub fn link(&mut self, a: usize, b: usize) {
+        self.links.push((a, b));
+    }
+
+    pub fn resolve(&self) -> Identities {
+        let mut union_find = UnionFind::new(self.value_sets.len());
+        for (a, b) in &self.links {
+            union_find.union(*a, *b);
+        }
+
+        // A value set can only be a subset of value sets that contain all of its values. Each
+        // value set is indexed by its rarest identifier value, so that the candidates of a value
+        // set are the value sets indexed by one of its own values, and no pairwise comparison of
+        // all value sets is needed.
+        let mut frequencies: HashMap<(&str, &str), usize> = HashMap::new();
+        for values in &self.value_sets {
+            for (identifier, value) in values {
+                *frequencies.entry((identifier, value)).or_insert(0) += 1;
+            }
+        }
+
+        let mut subset_index: HashMap<(&str, &str), Vec<usize>> = HashMap::new();
+        for (index, values) in self.value_sets.iter().enumerate() {
+            let rarest = values
+                .iter()
+                .map(|(identifier, value)| (identifier.as_str(), value.as_str()))
+                .min_by_key(|pair| (frequencies[pair], *pair));
+            if let Some(rarest) = rarest {
+                subset_index.entry(rarest).or_default().push(index);
+            }
+        }
+
+        for (index, values) in self.value_sets.iter().enumerate() {
+            for (identifier, value) in values {
+                let Some(candidates) = subset_index.get(&(identifier.as_str(), value.as_str()))
+                else {
+                    continue;
+                };
+
+                for candidate in candidates {
+                    if *candidate != index && is_subset(&self.value_sets[*candidate], values) {
+                        union_find.union(*candidate, index);
+                    }
+                }
+            }
+        }
+
+        self.collect_identities(&mut union_find)
+    }
+
+    fn collect_identities(&self, union_find: &mut UnionFind) -> Identities {
+        // Every value of each identity, and its smallest value set, which breaks ties between
+        // identities with the same values, e.g., value sets that a base model has linked
+        let mut merged: HashMap<usize, (BTreeMap<String, BTreeSet<String>>, &IdentifierValues)> =
+            HashMap::new();
+        for (index, values) in self.value_sets.iter().enumerate() {
+            let (all_values, smallest) = merged
+                .entry(union_find.find(index))
+                .or_insert_with(|| (BTreeMap::new(), values));
+            if values < *smallest {
+                *smallest = values;
+            }
+            for (identifier, value) in values {
+                all_values
+                    .entry(identifier.clone())
+                    .or_default()
+                    .insert(value.clone());
+            }
+        }
+
+        let mut identities: Vec<(usize, Identity, &IdentifierValues)> = merged
+            .into_iter()
+            .map(|(root, (all_values, smallest))| {
+                let mut values = IdentifierValues::new();
+                let mut conflicts = BTreeMap::new();
+                for (identifier, identifier_values) in all_values {
+                    if let Some(first) = identifier_values.first() {
+                        values.insert(identifier.clone(), first.clone());
+                    }
+                    if identifier_values.len() > 1 {
+                        conflicts.insert(identifier, identifier_values);
+                    }
+                }
+
+                (root, Identity { values, conflicts }, smallest)
+            })
+            .collect();
+        identities.sort_by(|(_, a, a_smallest), (_, b, b_smallest)| {
+            (&a.values, &a.conflicts, a_smallest).cmp(&(&b.values, &b.conflicts, b_smallest))
+        });
+
+        let positions: HashMap<usize, usize> = identities
+            .iter()
+            .enumerate()
+            .map(|(position, (root, _, _))| (*root, position))
+            .collect();
+        let identity_of = (0..self.value_sets.len())
+            .map(|index| positions[&union_find.find(index)])
+            .collect();
+
+        Identities {
+            identities: identities
+                .into_iter()
+                .map(|(_, identity, _)| identity)
+                .collect(),
+            identity_of,
+        }
+    }
+}
+
+fn is_subset(small: &IdentifierValues, large: &IdentifierValues) -> bool {
+    small.len() <= large.len()
+        && small
+            .iter()
+            .all(|(identifier, value)| large.get(identifier) == Some(value))
+}
+
+struct UnionFind {
+    parents: Vec<usize>,
+    ranks: Vec<u8>,
+}
+
+impl UnionFind {
+    fn new(len: usize) -> Self {
+        Self {
+            parents: (0..len).collect(),
+            ranks: vec![0; len],
+        }
+    }
+
+    fn find(&mut self, mut x: usize) -> usize {
+        while self.parents[x] != x {
+            self.parents[x] = self.parents[self.parents[x]];
+            x = self.parents[x];
+        }
+
+        x
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let (a, b) = (self.find(a), self.find(b));
+        if a == b {
+            return;
+        }
+
+        match self.ranks[a].cmp(&self.ranks[b]) {
+            std::cmp::Ordering::Less => self.parents[a] = b,
+            std::cmp::Ordering::Greater => self.parents[b] = a,
+            std::cmp::Ordering::Equal => {
+                self.parents[b] = a;
+                self.ranks[a] += 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::values;
+
+    #[test]
+    fn unifies_subsets_in_any_order() {
+        let sets: [IdentifierValues; 3] = [
+            values(&[("host", "a")]),
+            values(&[("host", "a"), ("port", "80")]),
+            values(&[("host", "b")]),
+        ];
+
+        for order in [[0, 1, 2], [1, 0, 2], [2, 1, 0]] {
+            let mut resolver = IdentityResolver::default();
+            let indexes: Vec<usize> = order
+                .iter()
+                .map(|i| resolver.add(sets[*i].clone()))
+                .collect();
+            let identities = resolver.resolve();
+
+            let identity_of = |i: usize| {
+                let position = order.iter().position(|o| *o == i).unwrap();
+                identities.identity_of(indexes[position])
+            };
+            assert_eq!(identities.identities.len(), 2);
+            assert_eq!(identity_of(0), identity_of(1));
+            assert_ne!(identity_of(0), identity_of(2));
+            assert_eq!(
+                identities.identities[identity_of(0)].values,
+                values(&[("host", "a"), ("port", "80")])
+            );
+        }
+    }
+
+    #[test]
+    fn bridges_components_transitively_and_reports_conflicts() {
+        let mut resolver = IdentityResolver::default();
+        let a = resolver.add(values(&[("host", "a"), ("port", "80")]));
+        let b = resolver.add(values(&[("host", "a"), ("port", "81")]));
+        let identities = resolver.resolve();
+        assert_ne!(identities.identity_of(a), identities.identity_of(b));
+
+        // Both are supersets of the bridge, so all three are the same component.
+        let bridge = resolver.add(values(&[("host", "a")]));
+        let identities = resolver.resolve();
+        assert_eq!(identities.identities.len(), 1);
+        assert_eq!(identities.identity_of(a), identities.identity_of(bridge));
+        assert_eq!(identities.identity_of(b), identities.identity_of(bridge));
+
+        let identity = &identities.identities[0];
+        assert_eq!(identity.values, values(&[("host", "a"), ("port", "80")]));
+        assert_eq!(
+            identity.conflicts,
+            BTreeMap::from([(
+                String::from("port"),
+                BTreeSet::from([String::from("80"), String::from("81")])
+            )])
+        );
+    }
+
+    #[test]
+    fn shares_value_sets_and_keeps_empty_values_apart() {
+        let mut resolver = IdentityResolver::default();
+        let a = resolver.add(values(&[("host", "a")]));
+        assert_eq!(resolver.add(values(&[("host", "a")])), a);
+
+        let empty = resolver.add(IdentifierValues::new());
+        assert_eq!(resolver.add(IdentifierValues::new()), empty);
+
+        let identities = resolver.resolve();
+        assert_eq!(identities.identities.len(), 2);
+        assert_ne!(identities.identity_of(a), identities.identity_of(empty));
+    }
+
+    #[test]
+    fn links_unrelated_value_sets() {
+        let mut resolver = IdentityResolver::default();
+        let a = resolver.add(values(&[("host", "a")]));
+        let b = resolver.add(values(&[("ip", "10.0.0.1")]));
+        let c = resolver.add(values(&[("host", "c")]));
+        resolver.link(a, b);
+
+        let identities = resolver.resolve();
+        assert_eq!(identities.identities.len(), 2);
+        assert_eq!(identities.identity_of(a), identities.identity_of(b));
+        assert_ne!(identities.identity_of(a), identities.identity_of(c));
+    }
+
+    #[test]
+    fn orders_identities_with_the_same_values_by_their_value_sets() {
+        let sets: [IdentifierValues; 4] = [
+            values(&[("x", "1"), ("y", "1")]),
+            values(&[("x", "2"), ("y", "2")]),
+            values(&[("x", "1"), ("y", "2")]),
+            values(&[("x", "2"), ("y", "1")]),
+        ];
+
+        for order in [[0, 1, 2, 3], [3, 2, 1, 0], [2, 0, 3, 1]] {
+            let mut resolver = IdentityResolver::default();
+            let mut indexes = [0; 4];
+            for i in order {
+                indexes[i] = resolver.add(sets[i].clone());
+            }
+            resolver.link(indexes[2], indexes[3]);
+            resolver.link(indexes[0], indexes[1]);
+
+            // Both identities have the same values and conflicts, so their smallest value sets
+            // decide the order.
+            let identities = resolver.resolve();
+            assert_eq!(identities.identities.len(), 2);
+            assert_eq!(
+                identities.identities[0].values,
+                identities.identities[1].values
+            );
+            assert_eq!(identities.identity_of(indexes[0]), 0);
+            assert_eq!(identities.identity_of(indexes[2]), 1);
+        }
+    }
+}
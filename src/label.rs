@@ -0,0 +1,428 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    error::Error,
+    fmt::{self, Display, Formatter},
+};
+
+use serde::Deserialize;
+
+use crate::{
+    conn::Model,
+    versioned_file::{self, VersionedFile},
+};
+
+#[derive(Debug)]
+pub enum LabelError {
+    UnsupportedVersion(u32),
+    InvalidTemplate(String),
+    EmptyMatch(String),
+}
+
+impl Error for LabelError {}
+
+impl Display for LabelError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            LabelError::UnsupportedVersion(v) => write!(
+                f,
+                "Unsupported label file version: {} (expected {})",
+                v,
+                LabelFile::VERSION
+            ),
+            LabelError::InvalidTemplate(t) => write!(f, "Invalid label template: {}", t),
+            LabelError::EmptyMatch(name) => write!(
+                f,
+                "The name \"{}\" matches no identifier values, so it would name every component",
+                name
+            ),
+        }
+    }
+}
+
+/// Names and label templates of components, e.g.,
+///
+/// ```yaml
+/// version: 1
+/// names:
+///   - name: Checkout API
+///     match: { service: checkout }
+/// templates:
+///   - schema: [db, table]
+///     template: "{db}.{table}"
+///   - template: "{service}@{host}:{port}"
+///   - template: "{service|host}"
+/// ```
+///
+/// Unknown fields are rejected, so that a misspelled field cannot silently drop labels.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LabelFile {
+    /// Checked by `read_versioned` before the rest of the file is read
+    #[serde(rename = "version")]
+    _version: u32,
+
+    #[serde(default)]
+    names: Vec<NameEntry>,
+
+    #[serde(default)]
+    templates: Vec<TemplateEntry>,
+}
+
+impl VersionedFile for LabelFile {
+    const VERSION: u32 = 1;
+
+    fn unsupported_version(version: u32) -> Box<dyn Error> {
+        Box::new(LabelError::UnsupportedVersion(version))
+    }
+}
+
+/// A persistent name of the components that have every identifier value of `match`
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct NameEntry {
+    name: String,
+
+    #[serde(rename = "match")]
+    values: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TemplateEntry {
+    /// Identifier keys that components must have exactly for the template to apply. Omitted
+    /// schemas apply to every component.
+    #[serde(default)]
+    schema: Option<BTreeSet<String>>,
+
+    template: String,
+}
+
+#[derive(Debug, PartialEq)]
+enum Segment {
+    Text(String),
+
+    /// Identifier keys whose first non-empty value is inserted
+    Value(Vec<String>),
+}
+
+#[derive(Debug)]
+struct Template {
+    schema: Option<BTreeSet<String>>,
+    segments: Vec<Segment>,
+}
+
+impl Template {
+    /// Fills the template with identifier values. Templates with a placeholder that no value
+    /// fills are not applied.
+    fn fill(&self, values: &BTreeMap<String, String>) -> Option<String> {
+        if self
+            .schema
+            .as_ref()
+            .is_some_and(|schema| !schema.iter().eq(values.keys()))
+        {
+            return None;
+        }
+
+        let mut label = String::new();
+        for segment in &self.segments {
+            match segment {
+                Segment::Text(text) => label.push_str(text),
+                Segment::Value(keys) => label.push_str(
+                    keys.iter()
+                        .filter_map(|key| values.get(key))
+                        .find(|value| !value.is_empty())?,
+                ),
+            }
+        }
+
+        Some(label)
+    }
+}
+
+/// How components are labeled. Names are matched first, and then templates are tried in order.
+/// Components that neither a name nor a template applies to are labeled by all of their
+/// identifier values.
+#[derive(Debug)]
+pub struct LabelRules {
+    names: Vec<NameEntry>,
+    templates: Vec<Template>,
+}
+
+impl LabelRules {
+    pub fn label(&self, values: &BTreeMap<String, String>) -> Option<String> {
+        let name = self.names.iter().find(|entry| {
+            entry
+                .values
+                .iter()
+                .all(|(key, value)| values.get(key) == Some(value))
+        });
+        if let Some(entry) = name {
+            return Some(entry.name.clone());
+        }
+
+        self.templates
+            .iter()
+            .find_map(|template| template.fill(values))
+    }
+}
+
+/// Reads label rules from a YAML or JSON file. The format is chosen by the extension.
+pub fn read_labels(file_path_str: &str) -> Result<LabelRules, Box<dyn Error>> {
+    let label_file: LabelFile = versioned_file::read_versioned(file_path_str)?;
+
+    if let Some(entry) = label_file
+        .names
+        .iter()
+        .find(|entry| entry.values.is_empty())
+    {
+        return Err(Box::new(LabelError::EmptyMatch(entry.name.clone())));
+    }
+
+    let templates = label_file
+        .templates
+        .into_iter()
+        .map(|entry| {
+            Ok(Template {
+                schema: entry.schema,
+                segments: parse_template(&entry.template)?,
+            })
+        })
+        .collect::<Result<Vec<_>, LabelError>>()?;
+
+    Ok(LabelRules {
+        names: label_file.names,
+        templates,
+    })
+}
+
+/// Parses a template such as "{service}@{host}:{port}". A placeholder may list fallback keys as
+/// "{service|host}", and "{{" and "}}" are literal braces.
+fn parse_template(template: &str) -> Result<Vec<Segment>, LabelError> {
+    let invalid = || LabelError::InvalidTemplate(template.to_string());
+
+    let mut segments: Vec<Segment> = Vec::new();
+    let mut text = String::new();
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                text.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                text.push('}');
+            }
+            '{' => {
+                let mut placeholder = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some('{') | None => return Err(invalid()),
+                        Some(c) => placeholder.push(c),
+                    }
+                }
+
+                let keys: Vec<String> = placeholder
+                    .split('|')
+                    .map(|key| key.trim().to_string())
+                    .collect();
+                if keys.iter().any(|key| key.is_empty()) {
+                    return Err(invalid());
+                }
+
+                if !text.is_empty() {
+                    segments.push(Segment::Text(std::mem::take(&mut text)));
+                }
+                segments.push(Segment::Value(keys));
+            }
+            '}' => return Err(invalid()),
+            c => text.push(c),
+        }
+    }
+    if !text.is_empty() {
+        segments.push(Segment::Text(text));
+    }
+
+    Ok(segments)
+}
+
+/// Labels the components of a model, which every output format draws instead of their
+/// identifier values. Without label rules, labels are cleared.
+pub fn apply_labels(model: &mut Model, rules: Option<&LabelRules>) {
+    for component in &mut model.components {
+        component.label = rules.and_then(|rules| rules.label(&component.component_values));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::test_support::{temp_path, values};
+
+    fn read_labels_from(name: &str, content: &str) -> Result<LabelRules, Box<dyn Error>> {
+        let path = temp_path(name);
+        fs::write(&path, content).unwrap();
+        let rules = read_labels(&path);
+        fs::remove_file(&path).unwrap();
+
+        rules
+    }
+
+    #[test]
+    fn parses_placeholders_fallbacks_and_literal_braces() {
+        assert_eq!(
+            parse_template("{{{service | host}}}@{port}").unwrap(),
+            [
+                Segment::Text(String::from("{")),
+                Segment::Value(vec![String::from("service"), String::from("host")]),
+                Segment::Text(String::from("}@")),
+                Segment::Value(vec![String::from("port")]),
+            ]
+        );
+        assert_eq!(
+            parse_template("{{}}").unwrap(),
+            [Segment::Text(String::from("{}"))]
+        );
+        assert!(parse_template("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn rejects_malformed_templates() {
+        for template in [
+            "{service",
+            "{service{host}}",
+            "service}",
+            "{}",
+            "{service|}",
+            "{ }",
+        ] {
+            assert!(
+                matches!(
+                    parse_template(template),
+                    Err(LabelError::InvalidTemplate(t)) if t == template
+                ),
+                "{:?} was parsed",
+                template
+            );
+        }
+    }
+
+    #[test]
+    fn fills_templates_with_the_first_non_empty_fallback() {
+        let template = Template {
+            schema: None,
+            segments: parse_template("{service|host}:{port}").unwrap(),
+        };
+
+        assert_eq!(
+            template.fill(&values(&[("service", ""), ("host", "a"), ("port", "80")])),
+            Some(String::from("a:80"))
+        );
+        assert_eq!(
+            template.fill(&values(&[
+                ("service", "api"),
+                ("host", "a"),
+                ("port", "80")
+            ])),
+            Some(String::from("api:80"))
+        );
+        assert_eq!(template.fill(&values(&[("host", "a")])), None);
+    }
+
+    #[test]
+    fn applies_schemas_exactly() {
+        let template = Template {
+            schema: Some(BTreeSet::from([String::from("db"), String::from("table")])),
+            segments: parse_template("{db}.{table}").unwrap(),
+        };
+
+        assert_eq!(
+            template.fill(&values(&[("db", "shop"), ("table", "orders")])),
+            Some(String::from("shop.orders"))
+        );
+        assert_eq!(template.fill(&values(&[("db", "shop")])), None);
+        assert_eq!(
+            template.fill(&values(&[
+                ("db", "shop"),
+                ("table", "orders"),
+                ("host", "a")
+            ])),
+            None
+        );
+    }
+
+    #[test]
+    fn names_take_precedence_over_templates() {
+        let rules = read_labels_from(
+            "labels.yaml",
+            "version: 1\n\
+             names:\n\
+             - name: Checkout API\n  match: { service: checkout }\n\
+             templates:\n\
+             - schema: [db]\n  template: \"db {db}\"\n\
+             - template: \"{service}@{host}\"\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            rules.label(&values(&[("service", "checkout"), ("host", "a")])),
+            Some(String::from("Checkout API"))
+        );
+        assert_eq!(
+            rules.label(&values(&[("service", "cart"), ("host", "a")])),
+            Some(String::from("cart@a"))
+        );
+        assert_eq!(
+            rules.label(&values(&[("db", "users")])),
+            Some(String::from("db users"))
+        );
+        assert_eq!(rules.label(&values(&[("service", "cart")])), None);
+    }
+
+    #[test]
+    fn rejects_unknown_fields() {
+        for yaml in [
+            "version: 1\ntempltes:\n- template: \"{host}\"\n",
+            "version: 1\nnames:\n- name: API\n  match: { service: api }\n  matches: { host: a }\n",
+            "version: 1\ntemplates:\n- schemas: [host]\n  template: \"{host}\"\n",
+        ] {
+            assert!(
+                serde_yaml::from_str::<LabelFile>(yaml).is_err(),
+                "{:?} was parsed",
+                yaml
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_label_files() {
+        let error = read_labels_from(
+            "empty.yaml",
+            "version: 1\nnames:\n- name: All\n  match: {}\n",
+        )
+        .unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<LabelError>(),
+            Some(LabelError::EmptyMatch(name)) if name == "All"
+        ));
+
+        let error = read_labels_from("version.json", r#"{"version": 2}"#).unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<LabelError>(),
+            Some(LabelError::UnsupportedVersion(2))
+        ));
+
+        let error = read_labels_from(
+            "template.yaml",
+            "version: 1\ntemplates:\n- template: \"{host\"\n",
+        )
+        .unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<LabelError>(),
+            Some(LabelError::InvalidTemplate(t)) if t == "{host"
+        ));
+    }
+}
@@ -0,0 +1,15 @@
+pub mod check;
+pub mod ci;
+pub mod cmd;
+pub mod config;
+pub mod conn;
+pub mod diff;
+pub mod identity;
+pub mod label;
+pub mod model;
+pub mod plugin;
+#[cfg(test)]
+mod test_support;
+pub mod trace;
+mod versioned_file;
+pub mod writer;
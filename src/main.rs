@@ -1,12 +1,5 @@
-use cmd::init_app;
 use env_logger::Env;
-
-mod cmd;
-mod config;
-mod model;
-mod plugin;
-mod ci;
-mod conn;
+use sarex::cmd::init_app;
 
 #[tokio::main]
 async fn main() {
@@ -0,0 +1,343 @@
+use async_trait::async_trait;
+use bson::{oid::ObjectId, DateTime};
+use log::debug;
+use serde::{Deserialize, Serialize};
+use std::{
+    error::Error,
+    fs::{self, OpenOptions},
+    io::{BufReader, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use super::{
+    drs::Dr,
+    mapping_rules::MappingRule,
+    projects::Project,
+    store::{Store, StoreError},
+};
+
+/// All documents of a file store. Field names follow the MongoDB collection names.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct FileData {
+    #[serde(default)]
+    projects: Vec<Project>,
+
+    #[serde(default)]
+    drs: Vec<Dr>,
+
+    #[serde(default)]
+    mappingrules: Vec<MappingRule>,
+}
+
+/// An embedded storage backend that keeps every document in a single JSON file, so that sarex
+/// can run without a MongoDB server.
+pub struct FileStore {
+    path: PathBuf,
+}
+
+impl FileStore {
+    pub fn open(path_str: &str) -> Result<Self, Box<dyn Error>> {
+        let path = PathBuf::from(path_str);
+
+        if !path.exists() {
+            if let Some(parent) = path.parent() {
+                if !parent.as_os_str().is_empty() {
+                    fs::create_dir_all(parent)?;
+                }
+            }
+            write_data(&path, &FileData::default())?;
+        } else {
+            read_data(&path)?; // Check if the file is a valid store
+        }
+        debug!("Opened file store!: {}", path.display());
+
+        Ok(Self { path })
+    }
+
+    fn read(&self) -> Result<FileData, Box<dyn Error>> {
+        read_data(&self.path)
+    }
+
+    fn write(&self, data: &FileData) -> Result<(), Box<dyn Error>> {
+        write_data(&self.path, data)
+    }
+}
+
+#[async_trait(?Send)]
+impl Store for FileStore {
+    async fn create_project(&self, name: String) -> Result<String, Box<dyn Error>> {
+        let mut data = self.read()?;
+
+        let id = ObjectId::new();
+        data.projects.push(Project {
+            id: Some(id),
+            name,
+            created_at: DateTime::now(),
+        });
+
+        self.write(&data)?;
+
+        Ok(id.to_hex())
+    }
+
+    async fn read_projects(&self) -> Result<Vec<Project>, Box<dyn Error>> {
+        Ok(self.read()?.projects)
+    }
+
+    async fn read_project(&self, id: &str) -> Result<Option<Project>, Box<dyn Error>> {
+        let oid = ObjectId::from_str(id)?;
+
+        Ok(self
+            .read()?
+            .projects
+            .into_iter()
+            .find(|project| project.id == Some(oid)))
+    }
+
+    async fn update_project(&self, id: &str, name: String) -> Result<String, Box<dyn Error>> {
+        let oid = ObjectId::from_str(id)?;
+        let mut data = self.read()?;
+
+        let project = data
+            .projects
+            .iter_mut()
+            .find(|p| p.id == Some(oid))
+            .ok_or_else(|| StoreError::NotFound(String::from(id)))?;
+        project.name = name;
+        self.write(&data)?;
+
+        Ok(String::from(id))
+    }
+
+    async fn create_drs(&self, drs: Vec<&Dr>) -> Result<(), Box<dyn Error>> {
+        let mut data = self.read()?;
+
+        for dr in drs {
+            let mut dr = dr.clone();
+            dr.id.get_or_insert_with(ObjectId::new);
+            data.drs.push(dr);
+        }
+
+        self.write(&data)
+    }
+
+    async fn create_mapping_rule(
+        &self,
+        mapping_rule: MappingRule,
+    ) -> Result<String, Box<dyn Error>> {
+        let mut data = self.read()?;
+
+        let id = ObjectId::new();
+        data.mappingrules.push(MappingRule {
+            id: Some(id),
+            ..mapping_rule
+        });
+
+        self.write(&data)?;
+
+        Ok(id.to_hex())
+    }
+
+    async fn read_mapping_rules(
+        &self,
+        project_id: &str,
+    ) -> Result<Vec<MappingRule>, Box<dyn Error>> {
+        Ok(self
+            .read()?
+            .mappingrules
+            .into_iter()
+            .filter(|mapping_rule| mapping_rule.project_id == project_id)
+            .collect())
+    }
+
+    async fn read_mapping_rule(&self, id: &str) -> Result<Option<MappingRule>, Box<dyn Error>> {
+        let oid = ObjectId::from_str(id)?;
+
+        Ok(self
+            .read()?
+            .mappingrules
+            .into_iter()
+            .find(|mapping_rule| mapping_rule.id == Some(oid)))
+    }
+
+    async fn update_mapping_rule(&self, mapping_rule: MappingRule) -> Result<(), Box<dyn Error>> {
+        let oid = mapping_rule.id.ok_or(StoreError::MissingId)?;
+        let mut data = self.read()?;
+
+        let existing = data
+            .mappingrules
+            .iter_mut()
+            .find(|m| m.id == Some(oid))
+            .ok_or_else(|| StoreError::NotFound(oid.to_hex()))?;
+        *existing = mapping_rule;
+
+        self.write(&data)
+    }
+
+    async fn upsert_mapping_rule(&self, mapping_rule: MappingRule) -> Result<(), Box<dyn Error>> {
+        let oid = mapping_rule.id.ok_or(StoreError::MissingId)?;
+        let mut data = self.read()?;
+
+        match data.mappingrules.iter_mut().find(|m| m.id == Some(oid)) {
+            Some(existing) => *existing = mapping_rule,
+            None => data.mappingrules.push(mapping_rule),
+        }
+
+        self.write(&data)
+    }
+
+    async fn delete_mapping_rule(&self, id: &str) -> Result<bool, Box<dyn Error>> {
+        let oid = ObjectId::from_str(id)?;
+        let mut data = self.read()?;
+
+        let len = data.mappingrules.len();
+        data.mappingrules
+            .retain(|mapping_rule| mapping_rule.id != Some(oid));
+        if data.mappingrules.len() == len {
+            return Ok(false);
+        }
+
+        self.write(&data)?;
+
+        Ok(true)
+    }
+}
+
+fn read_data(p: &Path) -> Result<FileData, Box<dyn Error>> {
+    let file = OpenOptions::new().read(true).open(p)?;
+    let reader = BufReader::new(file);
+    let data: FileData = serde_json::from_reader(reader)?;
+
+    Ok(data)
+}
+
+fn write_data(p: &Path, data: &FileData) -> Result<(), Box<dyn Error>> {
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(p)?;
+    file.write_all(serde_json::to_string_pretty(data)?.as_bytes())?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+
+    use super::*;
+    use crate::test_support::{self, temp_path};
+
+    fn mapping_rule(project_id: &str, procedure: &str) -> MappingRule {
+        let mut mapping_rule = test_support::mapping_rule();
+        mapping_rule.project_id = String::from(project_id);
+        mapping_rule.relation.project_id = String::from(project_id);
+        mapping_rule.procedure = String::from(procedure);
+        mapping_rule
+    }
+
+    #[test]
+    fn creates_the_store_file_and_its_directories() {
+        let dir = temp_path("nested");
+        let path = Path::new(&dir).join("store").join("sarex.json");
+        let store = FileStore::open(&path.display().to_string()).unwrap();
+
+        let id = block_on(store.create_project(String::from("shop"))).unwrap();
+        block_on(store.update_project(&id, String::from("webshop"))).unwrap();
+
+        // A reopened store reads the documents back from the file.
+        let store = FileStore::open(&path.display().to_string()).unwrap();
+        let projects = block_on(store.read_projects()).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(projects.len(), 1);
+        assert_eq!(projects[0].id.map(|id| id.to_hex()), Some(id));
+        assert_eq!(projects[0].name, "webshop");
+    }
+
+    #[test]
+    fn rejects_files_that_are_not_stores() {
+        let path = temp_path("invalid.json");
+        fs::write(&path, "{\"projects\": 1}").unwrap();
+        let result = FileStore::open(&path);
+        fs::remove_file(&path).unwrap();
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn rejects_updates_of_missing_documents() {
+        let path = temp_path("missing.json");
+        let store = FileStore::open(&path).unwrap();
+        let id = ObjectId::new();
+
+        let error = block_on(store.update_project(&id.to_hex(), String::from("shop"))).unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<StoreError>(),
+            Some(StoreError::NotFound(missing)) if *missing == id.to_hex()
+        ));
+
+        let mut missing_rule = mapping_rule("p1", "get_user");
+        missing_rule.id = Some(id);
+        let error = block_on(store.update_mapping_rule(missing_rule)).unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<StoreError>(),
+            Some(StoreError::NotFound(missing)) if *missing == id.to_hex()
+        ));
+
+        // Nothing is created by the failed updates.
+        assert!(block_on(store.read_projects()).unwrap().is_empty());
+        assert!(block_on(store.read_mapping_rules("p1")).unwrap().is_empty());
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn manages_mapping_rules_per_project() {
+        let path = temp_path("mapping-rules.json");
+        let store = FileStore::open(&path).unwrap();
+
+        let id = block_on(store.create_mapping_rule(mapping_rule("p1", "get_user"))).unwrap();
+        block_on(store.create_mapping_rule(mapping_rule("p2", "get_cart"))).unwrap();
+
+        let mut updated = block_on(store.read_mapping_rule(&id)).unwrap().unwrap();
+        updated.procedure = String::from("get_users");
+        block_on(store.update_mapping_rule(updated)).unwrap();
+
+        // Upserting keeps the IDs of imported mapping rules.
+        let mut imported = mapping_rule("p1", "put_user");
+        imported.id = Some(ObjectId::new());
+        block_on(store.upsert_mapping_rule(imported.clone())).unwrap();
+        block_on(store.upsert_mapping_rule(imported.clone())).unwrap();
+
+        let procedures = |project_id| {
+            let mut procedures: Vec<String> = block_on(store.read_mapping_rules(project_id))
+                .unwrap()
+                .into_iter()
+                .map(|mapping_rule| mapping_rule.procedure)
+                .collect();
+            procedures.sort();
+            procedures
+        };
+        assert_eq!(procedures("p1"), ["get_users", "put_user"]);
+        assert_eq!(procedures("p2"), ["get_cart"]);
+
+        // Mapping rules without IDs could never be read back.
+        let error =
+            block_on(store.upsert_mapping_rule(mapping_rule("p1", "get_cart"))).unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<StoreError>(),
+            Some(StoreError::MissingId)
+        ));
+        assert!(block_on(store.update_mapping_rule(mapping_rule("p1", "get_cart"))).is_err());
+        assert_eq!(procedures("p1"), ["get_users", "put_user"]);
+
+        assert!(block_on(store.delete_mapping_rule(&id)).unwrap());
+        assert!(!block_on(store.delete_mapping_rule(&id)).unwrap());
+        assert_eq!(procedures("p1"), ["put_user"]);
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,13 +1,13 @@
-use std::error::Error;
+use std::{
+    collections::HashSet,
+    error::Error,
+    fmt::{Display, Formatter},
+};
 
-use bson::{doc, oid::ObjectId};
-use futures::TryStreamExt;
-use mongodb::Collection;
+use bson::oid::ObjectId;
 use serde::{Deserialize, Serialize};
 
-use super::mongo::{get_default_db, get_mongo_client};
-
-const MAPPINGRULES_COL: &str = "mappingrules";
+use super::store;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct MappingRule {
@@ -67,23 +67,248 @@ impl Clone for Relation {
     }
 }
 
-pub async fn read_many(url: &str, project_id: &str) -> Result<Vec<MappingRule>, Box<dyn Error>> {
-    let collection = get_col(url).await?;
+#[derive(Debug)]
+pub enum MappingRuleError {
+    EmptyField(&'static str),
+    WhitespaceInProcedure(String),
+    EmptyIdentifier(&'static str),
+    DuplicateIdentifier(&'static str, String),
+    EmptyTargetSchema,
+}
+
+impl Error for MappingRuleError {}
+
+impl Display for MappingRuleError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MappingRuleError::EmptyField(field) => write!(f, "{} must not be empty", field),
+            MappingRuleError::WhitespaceInProcedure(procedure) => {
+                write!(
+                    f,
+                    "Procedure must not contain whitespace: \"{}\"",
+                    procedure
+                )
+            }
+            MappingRuleError::EmptyIdentifier(schema) => {
+                write!(f, "{} contains an empty identifier", schema)
+            }
+            MappingRuleError::DuplicateIdentifier(schema, identifier) => {
+                write!(
+                    f,
+                    "{} contains a duplicate identifier: {}",
+                    schema, identifier
+                )
+            }
+            MappingRuleError::EmptyTargetSchema => {
+                write!(f, "Target component identifier schema must not be empty")
+            }
+        }
+    }
+}
+
+impl MappingRule {
+    /// Checks that the mapping rule can be used to create connector instances.
+    pub fn validate(&self) -> Result<(), MappingRuleError> {
+        if self.procedure.is_empty() {
+            return Err(MappingRuleError::EmptyField("Procedure"));
+        }
+        if self.procedure.chars().any(char::is_whitespace) {
+            return Err(MappingRuleError::WhitespaceInProcedure(
+                self.procedure.clone(),
+            ));
+        }
+        if self.connector_type.is_empty() {
+            return Err(MappingRuleError::EmptyField("Connector type"));
+        }
+        if self.relation.id.is_empty() {
+            return Err(MappingRuleError::EmptyField("Relation ID"));
+        }
+        if self.relation.source.is_empty() {
+            return Err(MappingRuleError::EmptyField("Relation source"));
+        }
+        if self.relation.target.is_empty() {
+            return Err(MappingRuleError::EmptyField("Relation target"));
+        }
+        if self.target_component_identifier_schema.is_empty() {
+            return Err(MappingRuleError::EmptyTargetSchema);
+        }
+
+        validate_schema(
+            "Source component identifier schema",
+            &self.source_component_identifier_schema,
+        )?;
+        validate_schema(
+            "Target component identifier schema",
+            &self.target_component_identifier_schema,
+        )
+    }
 
-    let filter = doc! {"projectId": project_id};
-    let mut cursor = collection.find(filter, None).await?;
+    /// Checks that the mapping rule can be written to the database, where every mapping rule
+    /// belongs to a project. Mapping rules of rule files may have no project.
+    fn validate_stored(&self) -> Result<(), MappingRuleError> {
+        if self.project_id.is_empty() {
+            return Err(MappingRuleError::EmptyField("Project ID"));
+        }
+
+        self.validate()
+    }
+}
 
-    let mut mapping_rules: Vec<MappingRule> = Vec::new();
-    while let Some(mapping_rule) = cursor.try_next().await? {
-        mapping_rules.push(mapping_rule);
+fn validate_schema(name: &'static str, schema: &[String]) -> Result<(), MappingRuleError> {
+    let mut identifiers = HashSet::new();
+    for identifier in schema {
+        if identifier.is_empty() {
+            return Err(MappingRuleError::EmptyIdentifier(name));
+        }
+        if !identifiers.insert(identifier) {
+            return Err(MappingRuleError::DuplicateIdentifier(
+                name,
+                identifier.clone(),
+            ));
+        }
     }
 
-    Ok(mapping_rules)
+    Ok(())
+}
+
+pub async fn create(url: &str, mapping_rule: MappingRule) -> Result<String, Box<dyn Error>> {
+    mapping_rule.validate_stored()?;
+
+    store::open(url)
+        .await?
+        .create_mapping_rule(mapping_rule)
+        .await
+}
+
+pub async fn read_many(url: &str, project_id: &str) -> Result<Vec<MappingRule>, Box<dyn Error>> {
+    store::open(url).await?.read_mapping_rules(project_id).await
+}
+
+pub async fn read_one(url: &str, id: &str) -> Result<Option<MappingRule>, Box<dyn Error>> {
+    store::open(url).await?.read_mapping_rule(id).await
+}
+
+pub async fn update(url: &str, mapping_rule: MappingRule) -> Result<(), Box<dyn Error>> {
+    mapping_rule.validate_stored()?;
+
+    store::open(url)
+        .await?
+        .update_mapping_rule(mapping_rule)
+        .await
 }
 
-async fn get_col(url: &str) -> Result<Collection<MappingRule>, Box<dyn Error>> {
-    let client = get_mongo_client(url).await?;
-    let db = get_default_db(&client)?;
+/// Creates or replaces a mapping rule with its ID. It takes an open store, since imports upsert
+/// many mapping rules through one connection.
+pub async fn upsert(
+    store: &dyn store::Store,
+    mapping_rule: MappingRule,
+) -> Result<(), Box<dyn Error>> {
+    mapping_rule.validate_stored()?;
+
+    store.upsert_mapping_rule(mapping_rule).await
+}
+
+pub async fn delete(url: &str, id: &str) -> Result<bool, Box<dyn Error>> {
+    store::open(url).await?.delete_mapping_rule(id).await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use futures::executor::block_on;
+
+    use super::*;
+    use crate::{
+        model::{file::FileStore, store::Store},
+        test_support::{mapping_rule, temp_path},
+    };
 
-    Ok(db.collection(MAPPINGRULES_COL))
+    #[test]
+    fn accepts_complete_mapping_rules() {
+        assert!(mapping_rule().validate().is_ok());
+
+        // An empty source schema identifies sources by all of their values.
+        let mut mapping_rule = mapping_rule();
+        mapping_rule.source_component_identifier_schema.clear();
+        assert!(mapping_rule.validate().is_ok());
+    }
+
+    fn error_of(modify: impl FnOnce(&mut MappingRule)) -> String {
+        let mut mapping_rule = mapping_rule();
+        modify(&mut mapping_rule);
+        mapping_rule.validate().unwrap_err().to_string()
+    }
+
+    #[test]
+    fn rejects_incomplete_mapping_rules() {
+        assert_eq!(
+            error_of(|m| m.procedure.clear()),
+            "Procedure must not be empty"
+        );
+        assert_eq!(
+            error_of(|m| m.procedure = String::from("get user")),
+            "Procedure must not contain whitespace: \"get user\""
+        );
+        assert_eq!(
+            error_of(|m| m.connector_type.clear()),
+            "Connector type must not be empty"
+        );
+        assert_eq!(
+            error_of(|m| m.relation.id.clear()),
+            "Relation ID must not be empty"
+        );
+        assert_eq!(
+            error_of(|m| m.relation.target.clear()),
+            "Relation target must not be empty"
+        );
+        assert_eq!(
+            error_of(|m| m.target_component_identifier_schema.clear()),
+            "Target component identifier schema must not be empty"
+        );
+        assert_eq!(
+            error_of(|m| m.source_component_identifier_schema.push(String::new())),
+            "Source component identifier schema contains an empty identifier"
+        );
+        assert_eq!(
+            error_of(|m| m
+                .target_component_identifier_schema
+                .push(String::from("host"))),
+            "Target component identifier schema contains a duplicate identifier: host"
+        );
+    }
+
+    #[test]
+    fn requires_a_project_only_for_stored_mapping_rules() {
+        let mut mapping_rule = mapping_rule();
+        assert!(mapping_rule.validate_stored().is_ok());
+
+        mapping_rule.project_id.clear();
+        assert!(mapping_rule.validate().is_ok());
+        assert_eq!(
+            mapping_rule.validate_stored().unwrap_err().to_string(),
+            "Project ID must not be empty"
+        );
+    }
+
+    #[test]
+    fn validates_upserted_mapping_rules() {
+        let path = temp_path("upsert.json");
+        let store = FileStore::open(&path).unwrap();
+
+        let mut invalid = mapping_rule();
+        invalid.id = Some(ObjectId::new());
+        invalid.connector_type.clear();
+        let error = block_on(upsert(&store, invalid)).unwrap_err();
+        assert_eq!(error.to_string(), "Connector type must not be empty");
+
+        let mut valid = mapping_rule();
+        valid.id = Some(ObjectId::new());
+        block_on(upsert(&store, valid)).unwrap();
+
+        let stored = block_on(store.read_mapping_rules("project")).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(stored.len(), 1);
+        assert_eq!(stored[0].connector_type, "HTTP");
+    }
 }
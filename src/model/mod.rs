@@ -1,4 +1,7 @@
 pub mod drs;
+pub mod file;
 pub mod mapping_rules;
 pub mod mongo;
 pub mod projects;
+pub mod rule_file;
+pub mod store;
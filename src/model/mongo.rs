@@ -1,10 +1,28 @@
+use async_trait::async_trait;
+use futures::stream::TryStreamExt;
 use log::info;
-use mongodb::{bson::doc, options::ClientOptions, Client};
+use mongodb::{
+    bson::{doc, oid::ObjectId, DateTime},
+    options::{ClientOptions, ReplaceOptions},
+    Client, Collection, Database,
+};
 use std::{
     error::Error,
     fmt::{Display, Formatter},
+    str::FromStr,
+};
+
+use super::{
+    drs::Dr,
+    mapping_rules::MappingRule,
+    projects::Project,
+    store::{Store, StoreError},
 };
 
+const PROJECTS_COL: &str = "projects";
+const DRS_COL: &str = "drs";
+const MAPPINGRULES_COL: &str = "mappingrules";
+
 #[derive(Debug)]
 pub enum MongoError {
     NoDefaultDatabase,
@@ -22,6 +40,162 @@ impl Display for MongoError {
     }
 }
 
+pub struct MongoStore {
+    db: Database,
+}
+
+impl MongoStore {
+    pub async fn connect(url: &str) -> Result<Self, Box<dyn Error>> {
+        let client = get_mongo_client(url).await?;
+        let db = get_default_db(&client)?;
+
+        Ok(Self { db })
+    }
+
+    fn projects(&self) -> Collection<Project> {
+        self.db.collection(PROJECTS_COL)
+    }
+
+    fn drs(&self) -> Collection<Dr> {
+        self.db.collection(DRS_COL)
+    }
+
+    fn mapping_rules(&self) -> Collection<MappingRule> {
+        self.db.collection(MAPPINGRULES_COL)
+    }
+}
+
+#[async_trait(?Send)]
+impl Store for MongoStore {
+    async fn create_project(&self, name: String) -> Result<String, Box<dyn Error>> {
+        let new_project = Project {
+            id: None,
+            name,
+            created_at: DateTime::now(),
+        };
+
+        let result = self.projects().insert_one(new_project, None).await?;
+
+        match result.inserted_id.as_object_id() {
+            Some(id) => Ok(id.to_hex()),
+            None => Err(Box::new(MongoError::FailedToParseObjectId)),
+        }
+    }
+
+    async fn read_projects(&self) -> Result<Vec<Project>, Box<dyn Error>> {
+        let mut cursor = self.projects().find(None, None).await?;
+
+        let mut projects: Vec<Project> = Vec::new();
+        while let Some(project) = cursor.try_next().await? {
+            projects.push(project);
+        }
+
+        Ok(projects)
+    }
+
+    async fn read_project(&self, id: &str) -> Result<Option<Project>, Box<dyn Error>> {
+        let oid = ObjectId::from_str(id)?;
+        let filter = doc! {"_id": oid};
+
+        match self.projects().find_one(filter, None).await {
+            Ok(result) => Ok(result),
+            Err(e) => Err(Box::new(e)),
+        }
+    }
+
+    async fn update_project(&self, id: &str, name: String) -> Result<String, Box<dyn Error>> {
+        let oid = ObjectId::from_str(id)?;
+        let filter = doc! {"_id": oid};
+        let update = doc! {"$set": {"name": name}};
+
+        let result = self.projects().update_one(filter, update, None).await?;
+        if result.matched_count == 0 {
+            return Err(Box::new(StoreError::NotFound(String::from(id))));
+        }
+
+        Ok(String::from(id))
+    }
+
+    async fn create_drs(&self, drs: Vec<&Dr>) -> Result<(), Box<dyn Error>> {
+        self.drs().insert_many(drs, None).await?;
+
+        Ok(())
+    }
+
+    async fn create_mapping_rule(
+        &self,
+        mapping_rule: MappingRule,
+    ) -> Result<String, Box<dyn Error>> {
+        let result = self.mapping_rules().insert_one(mapping_rule, None).await?;
+
+        match result.inserted_id.as_object_id() {
+            Some(id) => Ok(id.to_hex()),
+            None => Err(Box::new(MongoError::FailedToParseObjectId)),
+        }
+    }
+
+    async fn read_mapping_rules(
+        &self,
+        project_id: &str,
+    ) -> Result<Vec<MappingRule>, Box<dyn Error>> {
+        let filter = doc! {"projectId": project_id};
+        let mut cursor = self.mapping_rules().find(filter, None).await?;
+
+        let mut mapping_rules: Vec<MappingRule> = Vec::new();
+        while let Some(mapping_rule) = cursor.try_next().await? {
+            mapping_rules.push(mapping_rule);
+        }
+
+        Ok(mapping_rules)
+    }
+
+    async fn read_mapping_rule(&self, id: &str) -> Result<Option<MappingRule>, Box<dyn Error>> {
+        let oid = ObjectId::from_str(id)?;
+        let filter = doc! {"_id": oid};
+
+        match self.mapping_rules().find_one(filter, None).await {
+            Ok(result) => Ok(result),
+            Err(e) => Err(Box::new(e)),
+        }
+    }
+
+    async fn update_mapping_rule(&self, mapping_rule: MappingRule) -> Result<(), Box<dyn Error>> {
+        let oid = mapping_rule.id.ok_or(StoreError::MissingId)?;
+        let filter = doc! {"_id": oid};
+
+        let result = self
+            .mapping_rules()
+            .replace_one(filter, mapping_rule, None)
+            .await?;
+        if result.matched_count == 0 {
+            return Err(Box::new(StoreError::NotFound(oid.to_hex())));
+        }
+
+        Ok(())
+    }
+
+    async fn upsert_mapping_rule(&self, mapping_rule: MappingRule) -> Result<(), Box<dyn Error>> {
+        let oid = mapping_rule.id.ok_or(StoreError::MissingId)?;
+        let filter = doc! {"_id": oid};
+        let options = ReplaceOptions::builder().upsert(true).build();
+
+        self.mapping_rules()
+            .replace_one(filter, mapping_rule, options)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn delete_mapping_rule(&self, id: &str) -> Result<bool, Box<dyn Error>> {
+        let oid = ObjectId::from_str(id)?;
+        let filter = doc! {"_id": oid};
+
+        let result = self.mapping_rules().delete_one(filter, None).await?;
+
+        Ok(result.deleted_count > 0)
+    }
+}
+
 pub async fn get_mongo_client(url: &str) -> Result<Client, Box<dyn Error>> {
     let opts = ClientOptions::parse(url).await?;
     let client = Client::with_options(opts)?;
@@ -34,7 +208,7 @@ pub async fn get_mongo_client(url: &str) -> Result<Client, Box<dyn Error>> {
     Ok(client)
 }
 
-pub fn get_default_db(client: &Client) -> Result<mongodb::Database, MongoError> {
+pub fn get_default_db(client: &Client) -> Result<Database, MongoError> {
     match client.default_database() {
         Some(db) => Ok(db),
         None => Err(MongoError::NoDefaultDatabase),
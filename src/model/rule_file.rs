@@ -0,0 +1,391 @@
+use bson::oid::ObjectId;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashSet},
+    error::Error,
+    fmt::{Display, Formatter},
+    fs::OpenOptions,
+    io::Write,
+    path::Path,
+    str::FromStr,
+};
+
+use super::mapping_rules::{MappingRule, Relation};
+use crate::versioned_file::{self, FileFormat, VersionedFile};
+
+#[derive(Debug)]
+pub enum RuleFileError {
+    UnsupportedVersion(u32),
+    InvalidMappingRuleId(String),
+    MissingMappingRuleId(String),
+    DuplicateMappingRule(String),
+    DuplicateRelation(String),
+    ConflictingRelation(String),
+    UnknownRelation(String),
+}
+
+impl Error for RuleFileError {}
+
+impl Display for RuleFileError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RuleFileError::UnsupportedVersion(v) => write!(
+                f,
+                "Unsupported rule file version: {} (expected {})",
+                v,
+                RuleFile::VERSION
+            ),
+            RuleFileError::InvalidMappingRuleId(id) => write!(f, "Invalid mapping rule ID: {}", id),
+            RuleFileError::MissingMappingRuleId(procedure) => {
+                write!(f, "Mapping rule without an ID: {}", procedure)
+            }
+            RuleFileError::DuplicateMappingRule(id) => write!(f, "Duplicate mapping rule: {}", id),
+            RuleFileError::DuplicateRelation(id) => write!(f, "Duplicate relation: {}", id),
+            RuleFileError::ConflictingRelation(id) => write!(
+                f,
+                "Relation with more than one source and target pair: {}",
+                id
+            ),
+            RuleFileError::UnknownRelation(id) => write!(f, "Unknown relation: {}", id),
+        }
+    }
+}
+
+/// A versioned, project-independent document of mapping rules and the relations they implement.
+///
+/// Unknown fields are rejected, so that a misspelled field cannot silently drop a value on import.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RuleFile {
+    version: u32,
+    relations: Vec<RelationEntry>,
+
+    #[serde(rename = "mappingRules")]
+    mapping_rules: Vec<MappingRuleEntry>,
+}
+
+impl VersionedFile for RuleFile {
+    const VERSION: u32 = 1;
+
+    fn unsupported_version(version: u32) -> Box<dyn Error> {
+        Box::new(RuleFileError::UnsupportedVersion(version))
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RelationEntry {
+    id: String,
+    source: String,
+    target: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MappingRuleEntry {
+    id: String,
+    procedure: String,
+    relation: String,
+
+    #[serde(rename = "connectorType")]
+    connector_type: String,
+
+    #[serde(rename = "sourceComponentIdentifierSchema", default)]
+    source_component_identifier_schema: Vec<String>,
+
+    #[serde(rename = "targetComponentIdentifierSchema")]
+    target_component_identifier_schema: Vec<String>,
+}
+
+/// Reads mapping rules from a rule file and assigns them to the project. The mapping rule IDs in
+/// the file are kept, so that execution traces keep referring to the same mapping rules.
+pub fn read(file_path_str: &str, project_id: &str) -> Result<Vec<MappingRule>, Box<dyn Error>> {
+    let rule_file: RuleFile = versioned_file::read_versioned(file_path_str)?;
+
+    let mut relations: BTreeMap<String, Relation> = BTreeMap::new();
+    for relation in rule_file.relations {
+        if relations.contains_key(&relation.id) {
+            return Err(Box::new(RuleFileError::DuplicateRelation(relation.id)));
+        }
+
+        relations.insert(
+            relation.id.clone(),
+            Relation {
+                id: relation.id,
+                source: relation.source,
+                target: relation.target,
+                project_id: String::from(project_id),
+            },
+        );
+    }
+
+    let mut ids: HashSet<ObjectId> = HashSet::new();
+    let mut mapping_rules: Vec<MappingRule> = Vec::new();
+    for entry in rule_file.mapping_rules {
+        let id = ObjectId::from_str(&entry.id)
+            .map_err(|_| RuleFileError::InvalidMappingRuleId(entry.id.clone()))?;
+        if !ids.insert(id) {
+            return Err(Box::new(RuleFileError::DuplicateMappingRule(entry.id)));
+        }
+
+        let relation = relations
+            .get(&entry.relation)
+            .ok_or_else(|| RuleFileError::UnknownRelation(entry.relation.clone()))?;
+
+        let mapping_rule = MappingRule {
+            id: Some(id),
+            project_id: String::from(project_id),
+            procedure: entry.procedure,
+            relation: relation.clone(),
+            connector_type: entry.connector_type,
+            source_component_identifier_schema: entry.source_component_identifier_schema,
+            target_component_identifier_schema: entry.target_component_identifier_schema,
+        };
+        mapping_rule.validate()?;
+
+        mapping_rules.push(mapping_rule);
+    }
+
+    Ok(mapping_rules)
+}
+
+/// Writes mapping rules to a rule file. The format is chosen by the file extension. Mapping rules
+/// without IDs and relations with conflicting sources or targets are rejected, since the file
+/// could not represent them.
+pub fn write(file_path_str: &str, mapping_rules: &[MappingRule]) -> Result<(), Box<dyn Error>> {
+    let p = Path::new(file_path_str);
+    let format = versioned_file::get_file_format(p)?;
+
+    let mut relations: BTreeMap<String, RelationEntry> = BTreeMap::new();
+    let mut entries: Vec<MappingRuleEntry> = Vec::new();
+    for mapping_rule in mapping_rules {
+        let id = match mapping_rule.id {
+            Some(id) => id.to_hex(),
+            None => {
+                return Err(Box::new(RuleFileError::MissingMappingRuleId(
+                    mapping_rule.procedure.clone(),
+                )))
+            }
+        };
+
+        let relation = relations
+            .entry(mapping_rule.relation.id.clone())
+            .or_insert_with(|| RelationEntry {
+                id: mapping_rule.relation.id.clone(),
+                source: mapping_rule.relation.source.clone(),
+                target: mapping_rule.relation.target.clone(),
+            });
+        if relation.source != mapping_rule.relation.source
+            || relation.target != mapping_rule.relation.target
+        {
+            return Err(Box::new(RuleFileError::ConflictingRelation(
+                relation.id.clone(),
+            )));
+        }
+
+        entries.push(MappingRuleEntry {
+            id,
+            procedure: mapping_rule.procedure.clone(),
+            relation: mapping_rule.relation.id.clone(),
+            connector_type: mapping_rule.connector_type.clone(),
+            source_component_identifier_schema: mapping_rule
+                .source_component_identifier_schema
+                .clone(),
+            target_component_identifier_schema: mapping_rule
+                .target_component_identifier_schema
+                .clone(),
+        });
+    }
+    entries.sort_by(|a, b| a.id.cmp(&b.id));
+
+    let rule_file = RuleFile {
+        version: RuleFile::VERSION,
+        relations: relations.into_values().collect(),
+        mapping_rules: entries,
+    };
+
+    let result = match format {
+        FileFormat::Json => serde_json::to_string_pretty(&rule_file)?,
+        FileFormat::Yaml => serde_yaml::to_string(&rule_file)?,
+    };
+
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(p)?;
+    file.write_all(result.as_bytes())?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::{
+        test_support::{self, temp_path},
+        versioned_file::VersionedFileError,
+    };
+
+    fn mapping_rule(id: &str, relation: &str) -> MappingRule {
+        let mut mapping_rule = test_support::mapping_rule();
+        mapping_rule.id = Some(ObjectId::from_str(id).unwrap());
+        mapping_rule.relation.id = String::from(relation);
+        mapping_rule
+    }
+
+    #[test]
+    fn round_trips_json_and_yaml() {
+        let mapping_rules = vec![
+            mapping_rule("65f1f77bcf86cd7994390112", "r1"),
+            mapping_rule("65f1f77bcf86cd7994390111", "r1"),
+        ];
+
+        for name in ["rules.json", "rules.yaml", "rules.yml"] {
+            let path = temp_path(name);
+            write(&path, &mapping_rules).unwrap();
+            let read_rules = read(&path, "new-project").unwrap();
+            fs::remove_file(&path).unwrap();
+
+            // Mapping rules are written in the order of their IDs.
+            assert_eq!(read_rules.len(), 2);
+            assert_eq!(read_rules[0].id, mapping_rules[1].id);
+            assert_eq!(read_rules[1].id, mapping_rules[0].id);
+            for read_rule in &read_rules {
+                assert_eq!(read_rule.project_id, "new-project");
+                assert_eq!(read_rule.relation.project_id, "new-project");
+                assert_eq!(read_rule.relation.id, "r1");
+                assert_eq!(read_rule.relation.source, "frontend");
+                assert_eq!(read_rule.relation.target, "users");
+                assert_eq!(read_rule.procedure, "get_user");
+                assert_eq!(read_rule.connector_type, "HTTP");
+                assert_eq!(read_rule.source_component_identifier_schema, ["host"]);
+                assert_eq!(
+                    read_rule.target_component_identifier_schema,
+                    ["host", "port"]
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_unsupported_formats_and_versions() {
+        let error = write(&temp_path("rules.txt"), &[]).unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<VersionedFileError>(),
+            Some(VersionedFileError::UnsupportedFormat(_))
+        ));
+
+        let path = temp_path("version.json");
+        fs::write(
+            &path,
+            r#"{"version": 2, "relations": [], "mappingRules": []}"#,
+        )
+        .unwrap();
+        let error = read(&path, "project").unwrap_err();
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(
+            error.downcast_ref::<RuleFileError>(),
+            Some(RuleFileError::UnsupportedVersion(2))
+        ));
+    }
+
+    #[test]
+    fn rejects_mapping_rules_that_the_file_cannot_represent() {
+        let path = temp_path("lossy.yaml");
+
+        let mut without_id = test_support::mapping_rule();
+        without_id.procedure = String::from("put_user");
+        let mapping_rules = [mapping_rule("65f1f77bcf86cd7994390111", "r1"), without_id];
+        let error = write(&path, &mapping_rules).unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<RuleFileError>(),
+            Some(RuleFileError::MissingMappingRuleId(procedure)) if procedure == "put_user"
+        ));
+
+        let mut conflicting = mapping_rule("65f1f77bcf86cd7994390112", "r1");
+        conflicting.relation.target = String::from("orders");
+        let mapping_rules = [mapping_rule("65f1f77bcf86cd7994390111", "r1"), conflicting];
+        let error = write(&path, &mapping_rules).unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<RuleFileError>(),
+            Some(RuleFileError::ConflictingRelation(id)) if id == "r1"
+        ));
+
+        assert!(!Path::new(&path).exists());
+    }
+
+    fn read_error(name: &str, content: &str) -> Box<dyn Error> {
+        let path = temp_path(name);
+        fs::write(&path, content).unwrap();
+        let error = read(&path, "project").unwrap_err();
+        fs::remove_file(&path).unwrap();
+
+        error
+    }
+
+    #[test]
+    fn rejects_unknown_fields() {
+        for yaml in [
+            "version: 1\nrelations: []\nmappingRules: []\nmappingRule: []\n",
+            "version: 1\nrelations: [{id: r1, source: a, target: b, sorce: c}]\nmappingRules: []\n",
+            "version: 1\nrelations: [{id: r1, source: a, target: b}]\nmappingRules:\n\
+             - {id: 65f1f77bcf86cd7994390111, procedure: p, relation: r1, connectorType: HTTP, \
+             targetComponentIdentifierSchema: [host], sourceComponentIdentifierSchemas: [host]}\n",
+        ] {
+            assert!(
+                serde_yaml::from_str::<RuleFile>(yaml).is_err(),
+                "{:?} was parsed",
+                yaml
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_inconsistent_rule_files() {
+        const RULE: &str = "procedure: p, relation: r1, connectorType: HTTP, \
+                            targetComponentIdentifierSchema: [host]";
+
+        let error = read_error(
+            "duplicate.yaml",
+            &format!(
+                "version: 1\nrelations: [{{id: r1, source: a, target: b}}]\nmappingRules:\n\
+                 - {{id: 65f1f77bcf86cd7994390111, {0}}}\n\
+                 - {{id: 65f1f77bcf86cd7994390111, {0}}}\n",
+                RULE
+            ),
+        );
+        assert!(matches!(
+            error.downcast_ref::<RuleFileError>(),
+            Some(RuleFileError::DuplicateMappingRule(_))
+        ));
+
+        let error = read_error(
+            "unknown.yaml",
+            &format!(
+                "version: 1\nrelations: []\nmappingRules:\n\
+                 - {{id: 65f1f77bcf86cd7994390111, {}}}\n",
+                RULE
+            ),
+        );
+        assert!(matches!(
+            error.downcast_ref::<RuleFileError>(),
+            Some(RuleFileError::UnknownRelation(_))
+        ));
+
+        let error = read_error(
+            "invalid.yaml",
+            &format!(
+                "version: 1\nrelations: [{{id: r1, source: a, target: b}}]\nmappingRules:\n\
+                 - {{id: rule, {}}}\n",
+                RULE
+            ),
+        );
+        assert!(matches!(
+            error.downcast_ref::<RuleFileError>(),
+            Some(RuleFileError::InvalidMappingRuleId(_))
+        ));
+    }
+}
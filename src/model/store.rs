@@ -0,0 +1,105 @@
+use async_trait::async_trait;
+use std::{
+    error::Error,
+    fmt::{Display, Formatter},
+};
+
+use super::{
+    drs::Dr, file::FileStore, mapping_rules::MappingRule, mongo::MongoStore, projects::Project,
+};
+
+const MONGO_SCHEMES: [&str; 2] = ["mongodb://", "mongodb+srv://"];
+const FILE_SCHEME: &str = "file://";
+
+#[derive(Debug)]
+pub enum StoreError {
+    NoDbUrlSet,
+    UnsupportedScheme(String),
+    MissingId,
+    NotFound(String),
+}
+
+impl Error for StoreError {}
+
+impl Display for StoreError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StoreError::NoDbUrlSet => write!(f, "No database URL is set"),
+            StoreError::UnsupportedScheme(url) => write!(
+                f,
+                "Unsupported database URL: {} (expected mongodb://, mongodb+srv:// or file://)",
+                url
+            ),
+            StoreError::MissingId => write!(f, "The document has no ID"),
+            StoreError::NotFound(id) => write!(f, "No document with the ID: {}", id),
+        }
+    }
+}
+
+/// A storage backend for projects, dependency relations and mapping rules.
+#[async_trait(?Send)]
+pub trait Store {
+    async fn create_project(&self, name: String) -> Result<String, Box<dyn Error>>;
+    async fn read_projects(&self) -> Result<Vec<Project>, Box<dyn Error>>;
+    async fn read_project(&self, id: &str) -> Result<Option<Project>, Box<dyn Error>>;
+    async fn update_project(&self, id: &str, name: String) -> Result<String, Box<dyn Error>>;
+
+    async fn create_drs(&self, drs: Vec<&Dr>) -> Result<(), Box<dyn Error>>;
+
+    async fn create_mapping_rule(
+        &self,
+        mapping_rule: MappingRule,
+    ) -> Result<String, Box<dyn Error>>;
+    async fn read_mapping_rules(
+        &self,
+        project_id: &str,
+    ) -> Result<Vec<MappingRule>, Box<dyn Error>>;
+    async fn read_mapping_rule(&self, id: &str) -> Result<Option<MappingRule>, Box<dyn Error>>;
+    async fn update_mapping_rule(&self, mapping_rule: MappingRule) -> Result<(), Box<dyn Error>>;
+    async fn upsert_mapping_rule(&self, mapping_rule: MappingRule) -> Result<(), Box<dyn Error>>;
+    async fn delete_mapping_rule(&self, id: &str) -> Result<bool, Box<dyn Error>>;
+}
+
+/// Opens the storage backend selected by the scheme of the database URL.
+/// `mongodb://` and `mongodb+srv://` URLs use MongoDB, and `file://<path>` URLs use an embedded
+/// JSON file store at `<path>`.
+pub async fn open(url: &str) -> Result<Box<dyn Store>, Box<dyn Error>> {
+    if url.is_empty() {
+        return Err(Box::new(StoreError::NoDbUrlSet));
+    }
+
+    if MONGO_SCHEMES.iter().any(|scheme| url.starts_with(scheme)) {
+        return Ok(Box::new(MongoStore::connect(url).await?));
+    }
+
+    if let Some(path) = url.strip_prefix(FILE_SCHEME) {
+        return Ok(Box::new(FileStore::open(path)?));
+    }
+
+    Err(Box::new(StoreError::UnsupportedScheme(url.to_string())))
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+
+    use super::*;
+
+    #[test]
+    fn rejects_missing_and_unsupported_urls() {
+        assert!(matches!(
+            block_on(open(""))
+                .err()
+                .unwrap()
+                .downcast_ref::<StoreError>(),
+            Some(StoreError::NoDbUrlSet)
+        ));
+        assert!(matches!(
+            block_on(open("postgres://localhost/sarex"))
+                .err()
+                .unwrap()
+                .downcast_ref::<StoreError>(),
+            Some(StoreError::UnsupportedScheme(_))
+        ));
+    }
+}
@@ -0,0 +1,47 @@
+//! Fixtures and helpers shared by the unit tests
+
+use std::{
+    env, process,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crate::model::mapping_rules::{MappingRule, Relation};
+
+/// A temporary path that is unique to each call, so that tests running in parallel never share a
+/// file. Tests remove what they create there.
+pub fn temp_path(name: &str) -> String {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let n = COUNTER.fetch_add(1, Ordering::Relaxed);
+
+    env::temp_dir()
+        .join(format!("sarex-{}-{}-{}", process::id(), n, name))
+        .display()
+        .to_string()
+}
+
+/// Identifier or context values from pairs, as a `HashMap` or a `BTreeMap`
+pub fn values<T: FromIterator<(String, String)>>(pairs: &[(&str, &str)]) -> T {
+    pairs
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect()
+}
+
+/// A mapping rule without an ID that maps "get_user" to the relation "r1" from "frontend" to
+/// "users" over HTTP. Sources are identified by host, and targets by host and port.
+pub fn mapping_rule() -> MappingRule {
+    MappingRule {
+        id: None,
+        project_id: String::from("project"),
+        procedure: String::from("get_user"),
+        relation: Relation {
+            id: String::from("r1"),
+            source: String::from("frontend"),
+            target: String::from("users"),
+            project_id: String::from("project"),
+        },
+        connector_type: String::from("HTTP"),
+        source_component_identifier_schema: vec![String::from("host")],
+        target_component_identifier_schema: vec![String::from("host"), String::from("port")],
+    }
+}
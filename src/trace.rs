@@ -0,0 +1,568 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    error::Error,
+    fmt::{self, Display, Formatter},
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    vec,
+};
+
+use flate2::read::MultiGzDecoder;
+use glob::Pattern;
+use serde::{Deserialize, Serialize};
+use serde_json::error::Category;
+use walkdir::WalkDir;
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ExecutionTrace {
+    pub id: String, // <MappingRuleId,Procedure,Index>
+
+    #[serde(rename = "sourceValues")]
+    pub source_values: HashMap<String, String>,
+
+    #[serde(rename = "targetValues")]
+    pub target_values: HashMap<String, String>,
+
+    /// Where the execution trace was read from. It is not part of the trace format.
+    #[serde(skip)]
+    pub location: TraceLocation,
+}
+
+/// A file and a 1-based line number of an execution trace.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct TraceLocation {
+    pub file: String,
+    pub line: usize,
+}
+
+impl Display for TraceLocation {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.file, self.line)
+    }
+}
+
+#[derive(Debug)]
+enum TraceError {
+    NoExecutionTraceFiles,
+    InvalidGlob(String),
+}
+
+impl Error for TraceError {}
+
+impl Display for TraceError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            TraceError::NoExecutionTraceFiles => write!(f, "No execution trace files found"),
+            TraceError::InvalidGlob(g) => write!(f, "Invalid glob pattern: {}", g),
+        }
+    }
+}
+
+/// Collects execution trace files from files and directories. Directories are walked recursively,
+/// and only files that match one of the `include` globs (all files, if none are given) and none of
+/// the `exclude` globs are collected from them. A glob matches either the file name or the path
+/// relative to the walked directory. Files that are given directly are always collected.
+pub fn find_execution_trace_files(
+    paths: &[String],
+    include: &[String],
+    exclude: &[String],
+) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let include = compile_globs(include)?;
+    let exclude = compile_globs(exclude)?;
+
+    let mut files: Vec<PathBuf> = Vec::new();
+    for path_str in paths {
+        let path = Path::new(path_str);
+        if !path.is_dir() {
+            files.push(path.to_path_buf());
+            continue;
+        }
+
+        let mut dir_files: Vec<PathBuf> = Vec::new();
+        for entry in WalkDir::new(path).follow_links(true) {
+            let entry = entry?;
+            if !entry.file_type().is_file() {
+                continue;
+            }
+
+            let relative_path = entry.path().strip_prefix(path)?;
+            let is_match = |patterns: &[Pattern]| {
+                patterns.iter().any(|pattern| {
+                    pattern.matches_path(relative_path)
+                        || pattern.matches(&entry.file_name().to_string_lossy())
+                })
+            };
+
+            if (include.is_empty() || is_match(&include)) && !is_match(&exclude) {
+                dir_files.push(entry.into_path());
+            }
+        }
+
+        dir_files.sort();
+        files.extend(dir_files);
+    }
+
+    if files.is_empty() {
+        return Err(Box::new(TraceError::NoExecutionTraceFiles));
+    }
+
+    Ok(files)
+}
+
+fn compile_globs(globs: &[String]) -> Result<Vec<Pattern>, TraceError> {
+    globs
+        .iter()
+        .map(|g| Pattern::new(g).map_err(|_| TraceError::InvalidGlob(g.clone())))
+        .collect()
+}
+
+/// Opens an execution trace file. Gzip and zstd compressed files are detected by their magic
+/// numbers and decompressed transparently.
+fn open_execution_trace_file(file_path: &Path) -> Result<Box<dyn BufRead>, Box<dyn Error>> {
+    let file = OpenOptions::new().read(true).open(file_path)?;
+    let mut reader = BufReader::new(file);
+
+    let header = reader.fill_buf()?;
+    if header.starts_with(&GZIP_MAGIC) {
+        Ok(Box::new(BufReader::new(MultiGzDecoder::new(reader))))
+    } else if header.starts_with(&ZSTD_MAGIC) {
+        Ok(Box::new(BufReader::new(zstd::Decoder::with_buffer(
+            reader,
+        )?)))
+    } else {
+        Ok(Box::new(reader))
+    }
+}
+
+/// Kinds of lines that cannot be read as execution traces.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum MalformedLineKind {
+    InvalidUtf8,
+    InvalidJson,
+    Truncated,
+    SchemaMismatch,
+}
+
+impl MalformedLineKind {
+    fn from_json_error(e: &serde_json::Error) -> Self {
+        match e.classify() {
+            Category::Data => MalformedLineKind::SchemaMismatch,
+            Category::Eof => MalformedLineKind::Truncated,
+            Category::Syntax | Category::Io => MalformedLineKind::InvalidJson,
+        }
+    }
+}
+
+impl Display for MalformedLineKind {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            MalformedLineKind::InvalidUtf8 => write!(f, "invalid UTF-8"),
+            MalformedLineKind::InvalidJson => write!(f, "invalid JSON"),
+            MalformedLineKind::Truncated => write!(f, "truncated JSON"),
+            MalformedLineKind::SchemaMismatch => write!(f, "not an execution trace"),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct MalformedLineError {
+    kind: MalformedLineKind,
+    location: TraceLocation,
+    message: String,
+}
+
+impl Error for MalformedLineError {}
+
+impl Display for MalformedLineError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Malformed execution trace ({}) at {}: {}",
+            self.kind, self.location, self.message
+        )
+    }
+}
+
+/// The number of skipped lines of a kind and where the first of them is.
+#[derive(Debug)]
+pub struct SkippedLines {
+    pub count: usize,
+    pub first: TraceLocation,
+}
+
+#[derive(Debug, Default)]
+pub struct TraceDiagnostics {
+    pub read_lines: usize,
+    pub skipped_lines: BTreeMap<MalformedLineKind, SkippedLines>,
+}
+
+impl TraceDiagnostics {
+    fn skip(&mut self, kind: MalformedLineKind, location: &TraceLocation) {
+        self.skipped_lines
+            .entry(kind)
+            .and_modify(|skipped| skipped.count += 1)
+            .or_insert_with(|| SkippedLines {
+                count: 1,
+                first: location.clone(),
+            });
+    }
+
+    pub fn skipped_count(&self) -> usize {
+        self.skipped_lines
+            .values()
+            .map(|skipped| skipped.count)
+            .sum()
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct TraceReadOptions {
+    /// Fails on the first malformed line instead of skipping it.
+    pub strict: bool,
+
+    /// A file path to which skipped lines are written as they are.
+    pub quarantine_file: Option<String>,
+}
+
+/// Reads execution traces line by line from the files in order, so that only one line is kept in
+/// memory at a time. Malformed lines are skipped and recorded in the diagnostics, unless the
+/// strict option is set. Blank lines are ignored. I/O errors always fail.
+pub fn read_execution_traces(
+    files: Vec<PathBuf>,
+    options: TraceReadOptions,
+) -> Result<ExecutionTraceReader, Box<dyn Error>> {
+    let quarantine = match &options.quarantine_file {
+        Some(p) => Some(BufWriter::new(
+            OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .open(p)?,
+        )),
+        None => None,
+    };
+
+    Ok(ExecutionTraceReader {
+        files: files.into_iter(),
+        current: None,
+        strict: options.strict,
+        quarantine,
+        diagnostics: TraceDiagnostics::default(),
+    })
+}
+
+pub struct ExecutionTraceReader {
+    files: vec::IntoIter<PathBuf>,
+    current: Option<OpenedFile>,
+    strict: bool,
+    quarantine: Option<BufWriter<File>>,
+    diagnostics: TraceDiagnostics,
+}
+
+struct OpenedFile {
+    file: String,
+    reader: Box<dyn BufRead>,
+    line: usize,
+}
+
+impl ExecutionTraceReader {
+    /// Flushes the quarantine file and returns what has been read so far.
+    pub fn finish(mut self) -> Result<TraceDiagnostics, Box<dyn Error>> {
+        if let Some(quarantine) = &mut self.quarantine {
+            quarantine.flush()?;
+        }
+
+        Ok(self.diagnostics)
+    }
+
+    fn reject(
+        &mut self,
+        kind: MalformedLineKind,
+        location: TraceLocation,
+        line: &[u8],
+        message: String,
+    ) -> Result<(), Box<dyn Error>> {
+        if self.strict {
+            return Err(Box::new(MalformedLineError {
+                kind,
+                location,
+                message,
+            }));
+        }
+
+        self.diagnostics.skip(kind, &location);
+        if let Some(quarantine) = &mut self.quarantine {
+            quarantine.write_all(line)?;
+            quarantine.write_all(b"\n")?;
+        }
+
+        Ok(())
+    }
+
+    fn next_execution_trace(&mut self) -> Result<Option<ExecutionTrace>, Box<dyn Error>> {
+        let mut buf: Vec<u8> = Vec::new();
+
+        loop {
+            let Some(current) = self.current.as_mut() else {
+                let Some(file_path) = self.files.next() else {
+                    return Ok(None);
+                };
+                self.current = Some(OpenedFile {
+                    file: file_path.display().to_string(),
+                    reader: open_execution_trace_file(&file_path)?,
+                    line: 0,
+                });
+                continue;
+            };
+
+            buf.clear();
+            if current.reader.read_until(b'\n', &mut buf)? == 0 {
+                self.current = None;
+                continue;
+            }
+            current.line += 1;
+            self.diagnostics.read_lines += 1;
+
+            let location = TraceLocation {
+                file: current.file.clone(),
+                line: current.line,
+            };
+
+            let line = trim_line_end(&buf);
+            let text = match std::str::from_utf8(line) {
+                Ok(text) => text,
+                Err(e) => {
+                    let line = line.to_vec();
+                    self.reject(
+                        MalformedLineKind::InvalidUtf8,
+                        location,
+                        &line,
+                        e.to_string(),
+                    )?;
+                    continue;
+                }
+            };
+            if text.trim().is_empty() {
+                continue;
+            }
+
+            match serde_json::from_str::<ExecutionTrace>(text) {
+                Ok(mut execution_trace) => {
+                    execution_trace.location = location;
+                    return Ok(Some(execution_trace));
+                }
+                Err(e) => {
+                    let line = line.to_vec();
+                    let kind = MalformedLineKind::from_json_error(&e);
+                    self.reject(kind, location, &line, e.to_string())?;
+                }
+            }
+        }
+    }
+}
+
+impl Iterator for ExecutionTraceReader {
+    type Item = Result<ExecutionTrace, Box<dyn Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_execution_trace().transpose()
+    }
+}
+
+fn trim_line_end(line: &[u8]) -> &[u8] {
+    let line = line.strip_suffix(b"\n").unwrap_or(line);
+    line.strip_suffix(b"\r").unwrap_or(line)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, io::Write};
+
+    use flate2::{write::GzEncoder, Compression};
+
+    use super::*;
+    use crate::test_support::temp_path;
+
+    /// Creates an empty directory for a test, which the test removes.
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = PathBuf::from(temp_path(name));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn trace_line(id: &str) -> String {
+        format!(
+            "{{\"id\": \"{}\", \"sourceValues\": {{\"host\": \"a\"}}, \"targetValues\": {{\"host\": \"b\"}}}}\n",
+            id
+        )
+    }
+
+    fn strings(paths: &[&str]) -> Vec<String> {
+        paths.iter().map(|p| p.to_string()).collect()
+    }
+
+    #[test]
+    fn finds_files_by_include_and_exclude_globs() {
+        let dir = temp_dir("find");
+        fs::create_dir_all(dir.join("b")).unwrap();
+        for file in [
+            "b/2.jsonl",
+            "1.jsonl",
+            "1.jsonl.gz",
+            "b/skip.jsonl",
+            "notes.txt",
+        ] {
+            fs::write(dir.join(file), "").unwrap();
+        }
+        let direct = dir.join("notes.txt").display().to_string();
+
+        let files = find_execution_trace_files(
+            &[dir.display().to_string(), direct.clone()],
+            &strings(&["*.jsonl", "*.gz"]),
+            &strings(&["b/skip*"]),
+        )
+        .unwrap();
+        let error = find_execution_trace_files(&[], &[], &[]).unwrap_err();
+        let invalid = find_execution_trace_files(&[], &strings(&["[a"]), &[]);
+        fs::remove_dir_all(&dir).unwrap();
+
+        // Files in directories are sorted, and files given directly are always collected.
+        assert_eq!(
+            files,
+            [
+                dir.join("1.jsonl"),
+                dir.join("1.jsonl.gz"),
+                dir.join("b/2.jsonl"),
+                PathBuf::from(direct),
+            ]
+        );
+        assert!(matches!(
+            error.downcast_ref::<TraceError>(),
+            Some(TraceError::NoExecutionTraceFiles)
+        ));
+        assert!(matches!(
+            invalid.unwrap_err().downcast_ref::<TraceError>(),
+            Some(TraceError::InvalidGlob(g)) if g == "[a"
+        ));
+    }
+
+    #[test]
+    fn reads_compressed_files_transparently() {
+        let dir = temp_dir("compressed");
+        let plain = dir.join("1.jsonl");
+        fs::write(&plain, trace_line("r_p_1")).unwrap();
+
+        let gzip = dir.join("2.jsonl.gz");
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(trace_line("r_p_2").as_bytes()).unwrap();
+        fs::write(&gzip, encoder.finish().unwrap()).unwrap();
+
+        let zstd = dir.join("3.jsonl.zst");
+        fs::write(
+            &zstd,
+            zstd::encode_all(trace_line("r_p_3").as_bytes(), 0).unwrap(),
+        )
+        .unwrap();
+
+        let ids: Vec<String> =
+            read_execution_traces(vec![plain, gzip, zstd], TraceReadOptions::default())
+                .unwrap()
+                .map(|execution_trace| execution_trace.unwrap().id)
+                .collect();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(ids, ["r_p_1", "r_p_2", "r_p_3"]);
+    }
+
+    /// Writes execution traces at lines 1 and 7, blank lines at 2 and 3, and malformed lines at
+    /// 4 to 6, 8 and 9.
+    fn write_malformed_lines(dir: &Path) -> PathBuf {
+        let path = dir.join("traces.jsonl");
+        let mut content = trace_line("r_p_1").into_bytes();
+        content.extend_from_slice(b"\r\n\n{\"id\": 1}\n\xff\n{\"id\": \n");
+        content.extend_from_slice(trace_line("r_p_2").as_bytes());
+        content.extend_from_slice(b"not json\n{\"id\": \"r_p_3\"}\n");
+        fs::write(&path, content).unwrap();
+        path
+    }
+
+    #[test]
+    fn skips_malformed_lines_and_quarantines_them() {
+        let dir = temp_dir("malformed");
+        let path = write_malformed_lines(&dir);
+        let quarantine = dir.join("quarantine.jsonl");
+
+        let mut reader = read_execution_traces(
+            vec![path.clone()],
+            TraceReadOptions {
+                strict: false,
+                quarantine_file: Some(quarantine.display().to_string()),
+            },
+        )
+        .unwrap();
+        let locations: Vec<TraceLocation> = reader
+            .by_ref()
+            .map(|execution_trace| execution_trace.unwrap().location)
+            .collect();
+        let diagnostics = reader.finish().unwrap();
+        let quarantined = fs::read(&quarantine).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        let file = path.display().to_string();
+        let at = |line| TraceLocation {
+            file: file.clone(),
+            line,
+        };
+        assert_eq!(locations, [at(1), at(7)]);
+
+        assert_eq!(diagnostics.read_lines, 9);
+        assert_eq!(diagnostics.skipped_count(), 5);
+        let skipped: Vec<(MalformedLineKind, usize, usize)> = diagnostics
+            .skipped_lines
+            .iter()
+            .map(|(kind, skipped)| (*kind, skipped.count, skipped.first.line))
+            .collect();
+        assert_eq!(
+            skipped,
+            [
+                (MalformedLineKind::InvalidUtf8, 1, 5),
+                (MalformedLineKind::InvalidJson, 1, 8),
+                (MalformedLineKind::Truncated, 1, 6),
+                (MalformedLineKind::SchemaMismatch, 2, 4),
+            ]
+        );
+
+        assert_eq!(
+            quarantined,
+            b"{\"id\": 1}\n\xff\n{\"id\": \nnot json\n{\"id\": \"r_p_3\"}\n"
+        );
+    }
+
+    #[test]
+    fn fails_on_the_first_malformed_line_in_strict_mode() {
+        let dir = temp_dir("strict");
+        let path = write_malformed_lines(&dir);
+
+        let results: Vec<Result<ExecutionTrace, Box<dyn Error>>> = read_execution_traces(
+            vec![path.clone()],
+            TraceReadOptions {
+                strict: true,
+                quarantine_file: None,
+            },
+        )
+        .unwrap()
+        .take(2)
+        .collect();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(results[0].is_ok());
+        let error = results[1].as_ref().unwrap_err();
+        let error = error.downcast_ref::<MalformedLineError>().unwrap();
+        assert_eq!(error.kind, MalformedLineKind::SchemaMismatch);
+        assert_eq!(error.location.line, 4);
+    }
+}
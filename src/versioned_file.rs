@@ -0,0 +1,164 @@
+//! YAML and JSON files whose formats are versioned, such as rule files, architecture rule files
+//! and label files
+
+use serde::{de::DeserializeOwned, Deserialize};
+use std::{
+    error::Error,
+    fmt::{Display, Formatter},
+    fs,
+    path::Path,
+};
+
+#[derive(Debug)]
+pub enum VersionedFileError {
+    UnsupportedFormat(String),
+}
+
+impl Error for VersionedFileError {}
+
+impl Display for VersionedFileError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VersionedFileError::UnsupportedFormat(p) => write!(
+                f,
+                "Unsupported file format: {} (expected .json, .yaml or .yml)",
+                p
+            ),
+        }
+    }
+}
+
+pub(crate) enum FileFormat {
+    Json,
+    Yaml,
+}
+
+/// Chooses the format of a file by its extension.
+pub(crate) fn get_file_format(p: &Path) -> Result<FileFormat, VersionedFileError> {
+    match p.extension().and_then(|ext| ext.to_str()) {
+        Some("json") => Ok(FileFormat::Json),
+        Some("yaml") | Some("yml") => Ok(FileFormat::Yaml),
+        _ => Err(VersionedFileError::UnsupportedFormat(
+            p.display().to_string(),
+        )),
+    }
+}
+
+/// A file whose format is versioned. Files are written with `VERSION`, and files with other
+/// versions are rejected.
+pub(crate) trait VersionedFile: DeserializeOwned {
+    const VERSION: u32;
+
+    /// The error of a file with another version
+    fn unsupported_version(version: u32) -> Box<dyn Error>;
+}
+
+/// The version of a file, which is read before the rest of the file, since other versions may
+/// have other fields
+#[derive(Deserialize)]
+struct Version {
+    version: u32,
+}
+
+/// Reads a versioned file. The format is chosen by the extension.
+pub(crate) fn read_versioned<T: VersionedFile>(file_path_str: &str) -> Result<T, Box<dyn Error>> {
+    let p = Path::new(file_path_str);
+    let format = get_file_format(p)?;
+    let content = fs::read_to_string(p)?;
+
+    let version: Version = match format {
+        FileFormat::Json => serde_json::from_str(&content)?,
+        FileFormat::Yaml => serde_yaml::from_str(&content)?,
+    };
+    if version.version != T::VERSION {
+        return Err(T::unsupported_version(version.version));
+    }
+
+    let file: T = match format {
+        FileFormat::Json => serde_json::from_str(&content)?,
+        FileFormat::Yaml => serde_yaml::from_str(&content)?,
+    };
+
+    Ok(file)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::temp_path;
+
+    #[derive(Debug, Deserialize)]
+    #[serde(deny_unknown_fields)]
+    struct TestFile {
+        version: u32,
+        names: Vec<String>,
+    }
+
+    #[derive(Debug)]
+    struct UnsupportedVersion(u32);
+
+    impl Error for UnsupportedVersion {}
+
+    impl Display for UnsupportedVersion {
+        fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+            write!(f, "Unsupported version: {}", self.0)
+        }
+    }
+
+    impl VersionedFile for TestFile {
+        const VERSION: u32 = 1;
+
+        fn unsupported_version(version: u32) -> Box<dyn Error> {
+            Box::new(UnsupportedVersion(version))
+        }
+    }
+
+    fn read(name: &str, content: &str) -> Result<TestFile, Box<dyn Error>> {
+        let path = temp_path(name);
+        fs::write(&path, content).unwrap();
+        let result = read_versioned(&path);
+        fs::remove_file(&path).unwrap();
+
+        result
+    }
+
+    #[test]
+    fn reads_json_and_yaml() {
+        let file = read("file.json", r#"{"version": 1, "names": ["a"]}"#).unwrap();
+        assert_eq!((file.version, file.names), (1, vec![String::from("a")]));
+        assert_eq!(
+            read("file.yml", "version: 1\nnames: [a, b]\n")
+                .unwrap()
+                .names,
+            ["a", "b"]
+        );
+    }
+
+    #[test]
+    fn checks_the_version_before_the_other_fields() {
+        // Other versions may have other fields, which are not reported as unknown fields.
+        for (name, content) in [
+            ("file.json", r#"{"version": 2, "entries": {}}"#),
+            ("file.yaml", "version: 2\nnames: {a: b}\n"),
+        ] {
+            let error = read(name, content).unwrap_err();
+            assert!(
+                matches!(
+                    error.downcast_ref::<UnsupportedVersion>(),
+                    Some(UnsupportedVersion(2))
+                ),
+                "{}",
+                error
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_unsupported_formats() {
+        let error = read_versioned::<TestFile>(&temp_path("file.txt")).unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<VersionedFileError>(),
+            Some(VersionedFileError::UnsupportedFormat(_))
+        ));
+    }
+}
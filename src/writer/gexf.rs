@@ -0,0 +1,117 @@
+use super::{escape_xml, get_identifier_types};
+use crate::conn::Model;
+
+/// Renders a model as GEXF 1.3, e.g., for Gephi. Identifier values become typed node attributes,
+/// and connector types and counts become edge attributes. The count is also the edge weight.
+pub fn get_gexf(model: &Model) -> String {
+    let identifier_types = get_identifier_types(model);
+
+    let mut s = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    s.push_str("<gexf xmlns=\"http://gexf.net/1.3\" version=\"1.3\">\n");
+    s.push_str("  <graph defaultedgetype=\"directed\">\n");
+
+    s.push_str("    <attributes class=\"node\">\n");
+    let mut attributes: Vec<&str> = Vec::new();
+    for (index, (identifier, identifier_type)) in identifier_types.iter().enumerate() {
+        s.push_str(&format!(
+            "      <attribute id=\"{}\" title=\"{}\" type=\"{}\"/>\n",
+            index,
+            escape_xml(identifier),
+            identifier_type.name()
+        ));
+        attributes.push(identifier);
+    }
+    s.push_str("    </attributes>\n");
+    s.push_str("    <attributes class=\"edge\">\n");
+    s.push_str(
+        "      <attribute id=\"connector_type\" title=\"connector_type\" type=\"string\"/>\n",
+    );
+    s.push_str("      <attribute id=\"count\" title=\"count\" type=\"long\"/>\n");
+    s.push_str("    </attributes>\n");
+
+    s.push_str("    <nodes>\n");
+    for component in &model.components {
+        let id = escape_xml(&component.id);
+        let label = escape_xml(component.label.as_deref().unwrap_or(&component.id));
+        s.push_str(&format!("      <node id=\"{}\" label=\"{}\">\n", id, label));
+        s.push_str("        <attvalues>\n");
+        for (index, identifier) in attributes.iter().enumerate() {
+            if let Some(value) = component.component_values.get(*identifier) {
+                s.push_str(&format!(
+                    "          <attvalue for=\"{}\" value=\"{}\"/>\n",
+                    index,
+                    escape_xml(value)
+                ));
+            }
+        }
+        s.push_str("        </attvalues>\n");
+        s.push_str("      </node>\n");
+    }
+    s.push_str("    </nodes>\n");
+
+    s.push_str("    <edges>\n");
+    for (index, connector) in model.connectors.iter().enumerate() {
+        let connector_type = escape_xml(&connector.connector_type);
+        s.push_str(&format!(
+            "      <edge id=\"{}\" source=\"{}\" target=\"{}\" label=\"{}\" weight=\"{}\">\n",
+            index,
+            escape_xml(&connector.source_component_id),
+            escape_xml(&connector.target_component_id),
+            connector_type,
+            connector.count.max(1)
+        ));
+        s.push_str("        <attvalues>\n");
+        s.push_str(&format!(
+            "          <attvalue for=\"connector_type\" value=\"{}\"/>\n",
+            connector_type
+        ));
+        s.push_str(&format!(
+            "          <attvalue for=\"count\" value=\"{}\"/>\n",
+            connector.count
+        ));
+        s.push_str("        </attvalues>\n");
+        s.push_str("      </edge>\n");
+    }
+    s.push_str("    </edges>\n");
+
+    s.push_str("  </graph>\n");
+    s.push_str("</gexf>\n");
+
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_typed_node_and_edge_attributes() {
+        let mut model: Model = serde_json::from_str(
+            r#"{
+                "components": [
+                    {"id": "a", "component_values": {"host": "a", "port": "80"}},
+                    {"id": "b", "component_values": {"host": "b&c"}}
+                ],
+                "connectors": [
+                    {"connector_type": "HTTP", "source_component_id": "a", "target_component_id": "b", "count": 3}
+                ]
+            }"#,
+        )
+        .unwrap();
+        model.components[1].label = Some(String::from("it's b"));
+
+        let gexf = get_gexf(&model);
+        for line in [
+            "<attribute id=\"0\" title=\"host\" type=\"string\"/>",
+            "<attribute id=\"1\" title=\"port\" type=\"long\"/>",
+            "<attribute id=\"connector_type\" title=\"connector_type\" type=\"string\"/>",
+            "<node id=\"a\" label=\"a\">\n        <attvalues>\n          <attvalue for=\"0\" value=\"a\"/>\n          <attvalue for=\"1\" value=\"80\"/>\n        </attvalues>",
+            "<node id=\"b\" label=\"it&apos;s b\">\n        <attvalues>\n          <attvalue for=\"0\" value=\"b&amp;c\"/>\n        </attvalues>",
+            "<edge id=\"0\" source=\"a\" target=\"b\" label=\"HTTP\" weight=\"3\">",
+            "<attvalue for=\"connector_type\" value=\"HTTP\"/>\n          <attvalue for=\"count\" value=\"3\"/>",
+        ] {
+            assert!(gexf.contains(line), "{}", gexf);
+        }
+        assert!(gexf.ends_with("</gexf>\n"));
+    }
+}
@@ -0,0 +1,108 @@
+use super::{escape_xml, get_identifier_types};
+use crate::conn::Model;
+
+/// Renders a model as GraphML, e.g., for yEd. Identifier values and labels become typed node
+/// attributes, and connector types and counts become edge attributes.
+pub fn get_graphml(model: &Model) -> String {
+    let identifier_types = get_identifier_types(model);
+
+    let mut s = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    s.push_str("<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n");
+
+    let mut keys: Vec<&str> = Vec::new();
+    for (index, (identifier, identifier_type)) in identifier_types.iter().enumerate() {
+        s.push_str(&format!(
+            "  <key id=\"n{}\" for=\"node\" attr.name=\"{}\" attr.type=\"{}\"/>\n",
+            index,
+            escape_xml(identifier),
+            identifier_type.name()
+        ));
+        keys.push(identifier);
+    }
+    if model.components.iter().any(|c| c.label.is_some()) {
+        s.push_str("  <key id=\"label\" for=\"node\" attr.name=\"label\" attr.type=\"string\"/>\n");
+    }
+    s.push_str("  <key id=\"connector_type\" for=\"edge\" attr.name=\"connector_type\" attr.type=\"string\"/>\n");
+    s.push_str("  <key id=\"count\" for=\"edge\" attr.name=\"count\" attr.type=\"long\"/>\n");
+
+    s.push_str("  <graph id=\"model\" edgedefault=\"directed\">\n");
+    for component in &model.components {
+        s.push_str(&format!(
+            "    <node id=\"{}\">\n",
+            escape_xml(&component.id)
+        ));
+        for (index, identifier) in keys.iter().enumerate() {
+            if let Some(value) = component.component_values.get(*identifier) {
+                s.push_str(&format!(
+                    "      <data key=\"n{}\">{}</data>\n",
+                    index,
+                    escape_xml(value)
+                ));
+            }
+        }
+        if let Some(label) = &component.label {
+            s.push_str(&format!(
+                "      <data key=\"label\">{}</data>\n",
+                escape_xml(label)
+            ));
+        }
+        s.push_str("    </node>\n");
+    }
+
+    for (index, connector) in model.connectors.iter().enumerate() {
+        s.push_str(&format!(
+            "    <edge id=\"e{}\" source=\"{}\" target=\"{}\">\n",
+            index,
+            escape_xml(&connector.source_component_id),
+            escape_xml(&connector.target_component_id)
+        ));
+        s.push_str(&format!(
+            "      <data key=\"connector_type\">{}</data>\n",
+            escape_xml(&connector.connector_type)
+        ));
+        s.push_str(&format!(
+            "      <data key=\"count\">{}</data>\n",
+            connector.count
+        ));
+        s.push_str("    </edge>\n");
+    }
+    s.push_str("  </graph>\n");
+    s.push_str("</graphml>\n");
+
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_typed_node_and_edge_data() {
+        let mut model: Model = serde_json::from_str(
+            r#"{
+                "components": [
+                    {"id": "a", "component_values": {"host": "a", "port": "80"}},
+                    {"id": "b", "component_values": {"host": "b&c"}}
+                ],
+                "connectors": [
+                    {"connector_type": "HTTP", "source_component_id": "a", "target_component_id": "b", "count": 3}
+                ]
+            }"#,
+        )
+        .unwrap();
+        model.components[1].label = Some(String::from("it's b"));
+
+        let graphml = get_graphml(&model);
+        for line in [
+            "<key id=\"n0\" for=\"node\" attr.name=\"host\" attr.type=\"string\"/>",
+            "<key id=\"n1\" for=\"node\" attr.name=\"port\" attr.type=\"long\"/>",
+            "<key id=\"label\" for=\"node\" attr.name=\"label\" attr.type=\"string\"/>",
+            "<node id=\"a\">\n      <data key=\"n0\">a</data>\n      <data key=\"n1\">80</data>\n    </node>",
+            "<node id=\"b\">\n      <data key=\"n0\">b&amp;c</data>\n      <data key=\"label\">it&apos;s b</data>\n    </node>",
+            "<edge id=\"e0\" source=\"a\" target=\"b\">\n      <data key=\"connector_type\">HTTP</data>\n      <data key=\"count\">3</data>\n    </edge>",
+        ] {
+            assert!(graphml.contains(line), "{}", graphml);
+        }
+        assert!(graphml.ends_with("</graphml>\n"));
+    }
+}
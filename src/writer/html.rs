@@ -0,0 +1,113 @@
+use std::error::Error;
+
+use serde_json::json;
+
+use crate::conn::Model;
+
+use super::svg;
+
+/// A viewer without external resources. The model and its layout replace the placeholders.
+const VIEWER_TEMPLATE: &str = include_str!("html_viewer.html");
+const MODEL_PLACEHOLDER: &str = "/*MODEL*/";
+const LAYOUT_PLACEHOLDER: &str = "/*LAYOUT*/";
+
+/// Renders a model as a single HTML file that can be viewed offline. The model is embedded as
+/// JSON with positions from the built-in layered layout, so that large models are laid out
+/// before they reach the browser, and the embedded script draws a pannable and zoomable graph
+/// with search, details of components and connectors, including their provenance, and toggles
+/// of connector types.
+pub fn get_html(model: &Model) -> Result<String, Box<dyn Error>> {
+    let model_layout = svg::layout_model(model);
+    let layout = json!({
+        "nodes": model_layout
+            .layout
+            .nodes
+            .iter()
+            .zip(&model_layout.sizes)
+            .zip(&model_layout.labels)
+            .map(|((center, size), lines)| json!({
+                "lines": lines,
+                "x": center.x,
+                "y": center.y,
+                "w": size.width,
+                "h": size.height,
+            }))
+            .collect::<Vec<_>>(),
+        "edges": model_layout
+            .layout
+            .edges
+            .iter()
+            .map(|points| points.iter().map(|p| [p.x, p.y]).collect::<Vec<_>>())
+            .collect::<Vec<_>>(),
+    });
+
+    // "<" is escaped, so that values such as "</script>" cannot end the script element.
+    let model_json = serde_json::to_string(model)?.replace('<', "\\u003c");
+    let layout_json = serde_json::to_string(&layout)?.replace('<', "\\u003c");
+
+    Ok(VIEWER_TEMPLATE
+        .replacen(MODEL_PLACEHOLDER, &model_json, 1)
+        .replacen(LAYOUT_PLACEHOLDER, &layout_json, 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn embeds_the_model_without_ending_the_script() {
+        let model: Model = serde_json::from_str(
+            r#"{
+                "components": [{"id": "c1", "component_values": {"host": "</script><b>"}}],
+                "connectors": []
+            }"#,
+        )
+        .unwrap();
+
+        let html = get_html(&model).unwrap();
+        assert!(!html.contains(MODEL_PLACEHOLDER));
+        assert!(!html.contains("</script><b>"));
+        assert!(html.contains(r#""host":"\u003c/script>\u003cb>""#));
+    }
+
+    #[test]
+    fn embeds_the_layout_of_the_model() {
+        let model: Model = serde_json::from_str(
+            r#"{
+                "components": [
+                    {"id": "c1", "component_values": {"host": "a"}},
+                    {"id": "c2", "component_values": {"host": "b"}}
+                ],
+                "connectors": [
+                    {"source_component_id": "c1", "target_component_id": "c2", "connector_type": "http", "count": 1},
+                    {"source_component_id": "c1", "target_component_id": "c3", "connector_type": "http", "count": 1}
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        let html = get_html(&model).unwrap();
+        assert!(!html.contains(LAYOUT_PLACEHOLDER));
+        let start = html
+            .find(r#"<script id="layout" type="application/json">"#)
+            .unwrap();
+        let layout_json = html[start..]
+            .split_once('>')
+            .unwrap()
+            .1
+            .split_once("</script>")
+            .unwrap()
+            .0;
+        let layout: serde_json::Value = serde_json::from_str(layout_json).unwrap();
+        assert_eq!(layout["nodes"].as_array().unwrap().len(), 2);
+        assert_eq!(layout["nodes"][0]["lines"], serde_json::json!(["host:a"]));
+        // The connector to the unknown component is not laid out.
+        assert_eq!(layout["edges"].as_array().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn the_template_has_one_of_each_placeholder() {
+        assert_eq!(VIEWER_TEMPLATE.matches(MODEL_PLACEHOLDER).count(), 1);
+        assert_eq!(VIEWER_TEMPLATE.matches(LAYOUT_PLACEHOLDER).count(), 1);
+    }
+}
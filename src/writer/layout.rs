@@ -0,0 +1,459 @@
+use std::collections::HashMap;
+
+/// The number of up and down sweeps of crossing reduction and coordinate assignment
+const SWEEPS: usize = 8;
+const NODE_GAP: f64 = 40.0;
+const LAYER_GAP: f64 = 70.0;
+const DUMMY_WIDTH: f64 = 20.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Point {
+    pub x: f64,
+    pub y: f64,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Size {
+    pub width: f64,
+    pub height: f64,
+}
+
+/// Positions of a laid out graph. The graph is drawn top-down, and every coordinate is
+/// non-negative.
+#[derive(Debug)]
+pub struct Layout {
+    /// The center of each node
+    pub nodes: Vec<Point>,
+
+    /// The points that each edge passes through from its source to its target. Self-loops have
+    /// no points, so that renderers can draw them beside their nodes.
+    pub edges: Vec<Vec<Point>>,
+
+    pub width: f64,
+    pub height: f64,
+}
+
+/// A node of the layered graph, which is either a node of the input graph or a dummy node that
+/// an edge spanning several layers passes through
+struct Vertex {
+    layer: usize,
+    width: f64,
+    height: f64,
+    x: f64,
+    upper: Vec<usize>,
+    lower: Vec<usize>,
+}
+
+/// Lays out a directed graph in layers (Sugiyama style): cycles are broken by reversing the
+/// back edges of a depth-first search, nodes are layered by the longest path from a source,
+/// edges that span several layers pass through dummy nodes, crossings are reduced with the
+/// barycenter heuristic, and nodes are moved towards the barycenters of their neighbors without
+/// overlapping.
+pub fn layered_layout(sizes: &[Size], edges: &[(usize, usize)]) -> Layout {
+    let n = sizes.len();
+
+    // The edges without self-loops, in the direction of the layers
+    let reversed = find_back_edges(n, edges);
+    let dag_edges: Vec<Option<(usize, usize)>> = edges
+        .iter()
+        .zip(&reversed)
+        .map(|(&(s, t), &is_reversed)| match (s == t, is_reversed) {
+            (true, _) => None,
+            (false, false) => Some((s, t)),
+            (false, true) => Some((t, s)),
+        })
+        .collect();
+
+    let layers_of = assign_layers(n, &dag_edges);
+    let mut vertices: Vec<Vertex> = sizes
+        .iter()
+        .zip(&layers_of)
+        .map(|(size, &layer)| Vertex {
+            layer,
+            width: size.width,
+            height: size.height,
+            x: 0.0,
+            upper: Vec::new(),
+            lower: Vec::new(),
+        })
+        .collect();
+
+    // Chains of vertices of the edges, in which edges between the same nodes share dummy nodes
+    let mut chains: HashMap<(usize, usize), Vec<usize>> = HashMap::new();
+    for &(s, t) in dag_edges.iter().flatten() {
+        if chains.contains_key(&(s, t)) {
+            continue;
+        }
+
+        let mut chain = vec![s];
+        for layer in layers_of[s] + 1..layers_of[t] {
+            vertices.push(Vertex {
+                layer,
+                width: DUMMY_WIDTH,
+                height: 0.0,
+                x: 0.0,
+                upper: Vec::new(),
+                lower: Vec::new(),
+            });
+            chain.push(vertices.len() - 1);
+        }
+        chain.push(t);
+
+        for pair in chain.windows(2) {
+            vertices[pair[0]].lower.push(pair[1]);
+            vertices[pair[1]].upper.push(pair[0]);
+        }
+        chains.insert((s, t), chain);
+    }
+
+    let layer_count = layers_of.iter().max().map_or(0, |max| max + 1);
+    let mut layers: Vec<Vec<usize>> = vec![Vec::new(); layer_count];
+    for (index, vertex) in vertices.iter().enumerate() {
+        layers[vertex.layer].push(index);
+    }
+
+    reduce_crossings(&vertices, &mut layers);
+    assign_x(&mut vertices, &layers);
+
+    // Layers are as high as their highest nodes.
+    let mut layer_ys: Vec<f64> = Vec::with_capacity(layer_count);
+    let mut y = NODE_GAP / 2.0;
+    for layer in &layers {
+        let height = layer
+            .iter()
+            .map(|v| vertices[*v].height)
+            .fold(0.0, f64::max);
+        layer_ys.push(y + height / 2.0);
+        y += height + LAYER_GAP;
+    }
+    let height = (y - LAYER_GAP + NODE_GAP / 2.0).max(0.0);
+
+    let min_x = vertices
+        .iter()
+        .map(|v| v.x - v.width / 2.0)
+        .fold(f64::INFINITY, f64::min);
+    let offset = if min_x.is_finite() {
+        NODE_GAP / 2.0 - min_x
+    } else {
+        0.0
+    };
+    // An empty graph has no margins either.
+    let width = if vertices.is_empty() {
+        0.0
+    } else {
+        vertices
+            .iter()
+            .map(|v| v.x + offset + v.width / 2.0)
+            .fold(0.0, f64::max)
+            + NODE_GAP
+    };
+
+    let center = |v: usize| Point {
+        x: vertices[v].x + offset,
+        y: layer_ys[vertices[v].layer],
+    };
+
+    let edge_points = edges
+        .iter()
+        .zip(&dag_edges)
+        .zip(&reversed)
+        .map(|((_, dag_edge), &is_reversed)| {
+            let Some(key) = dag_edge else {
+                return Vec::new();
+            };
+
+            let chain = &chains[key];
+            let mut points: Vec<Point> = chain.iter().map(|v| center(*v)).collect();
+            // Edges leave the bottoms of the upper nodes and enter the tops of the lower nodes.
+            if let (Some(first), Some(last)) = (chain.first(), chain.last()) {
+                points[0].y += vertices[*first].height / 2.0;
+                let end = points.len() - 1;
+                points[end].y -= vertices[*last].height / 2.0;
+            }
+            if is_reversed {
+                points.reverse();
+            }
+
+            points
+        })
+        .collect();
+
+    Layout {
+        nodes: (0..n).map(center).collect(),
+        edges: edge_points,
+        width,
+        height,
+    }
+}
+
+/// Finds edges that close cycles in a depth-first search, which become acyclic when reversed.
+fn find_back_edges(n: usize, edges: &[(usize, usize)]) -> Vec<bool> {
+    let mut outgoing: Vec<Vec<(usize, usize)>> = vec![Vec::new(); n];
+    for (index, &(s, t)) in edges.iter().enumerate() {
+        if s != t {
+            outgoing[s].push((t, index));
+        }
+    }
+
+    #[derive(Clone, Copy, PartialEq)]
+    enum State {
+        New,
+        OnStack,
+        Done,
+    }
+
+    let mut states = vec![State::New; n];
+    let mut reversed = vec![false; edges.len()];
+    for root in 0..n {
+        if states[root] != State::New {
+            continue;
+        }
+
+        // An iterative search, so that long chains cannot overflow the stack
+        states[root] = State::OnStack;
+        let mut stack: Vec<(usize, usize)> = vec![(root, 0)];
+        while let Some((v, next)) = stack.last_mut() {
+            let v = *v;
+            let Some(&(t, index)) = outgoing[v].get(*next) else {
+                states[v] = State::Done;
+                stack.pop();
+                continue;
+            };
+            *next += 1;
+
+            match states[t] {
+                State::New => {
+                    states[t] = State::OnStack;
+                    stack.push((t, 0));
+                }
+                State::OnStack => reversed[index] = true,
+                State::Done => {}
+            }
+        }
+    }
+
+    reversed
+}
+
+/// Assigns each node the length of the longest path from a source to it.
+fn assign_layers(n: usize, dag_edges: &[Option<(usize, usize)>]) -> Vec<usize> {
+    let mut outgoing: Vec<Vec<usize>> = vec![Vec::new(); n];
+    let mut in_degrees = vec![0; n];
+    for &(s, t) in dag_edges.iter().flatten() {
+        outgoing[s].push(t);
+        in_degrees[t] += 1;
+    }
+
+    let mut layers = vec![0; n];
+    let mut queue: Vec<usize> = (0..n).filter(|v| in_degrees[*v] == 0).collect();
+    while let Some(v) = queue.pop() {
+        for &t in &outgoing[v] {
+            layers[t] = layers[t].max(layers[v] + 1);
+            in_degrees[t] -= 1;
+            if in_degrees[t] == 0 {
+                queue.push(t);
+            }
+        }
+    }
+
+    layers
+}
+
+/// Orders each layer by the barycenters of the positions of neighbors in the previous layer,
+/// sweeping down and up alternately.
+fn reduce_crossings(vertices: &[Vertex], layers: &mut [Vec<usize>]) {
+    let mut positions: Vec<f64> = vec![0.0; vertices.len()];
+    let update_positions = |layer: &[usize], positions: &mut Vec<f64>| {
+        for (position, v) in layer.iter().enumerate() {
+            positions[*v] = position as f64;
+        }
+    };
+    for layer in layers.iter() {
+        update_positions(layer, &mut positions);
+    }
+
+    for sweep in 0..SWEEPS {
+        let down = sweep % 2 == 0;
+        let order: Vec<usize> = if down {
+            (1..layers.len()).collect()
+        } else {
+            (0..layers.len().saturating_sub(1)).rev().collect()
+        };
+
+        for index in order {
+            let layer = &mut layers[index];
+            let barycenters: HashMap<usize, f64> = layer
+                .iter()
+                .map(|v| {
+                    let neighbors = if down {
+                        &vertices[*v].upper
+                    } else {
+                        &vertices[*v].lower
+                    };
+                    let barycenter = if neighbors.is_empty() {
+                        positions[*v]
+                    } else {
+                        neighbors.iter().map(|u| positions[*u]).sum::<f64>()
+                            / neighbors.len() as f64
+                    };
+                    (*v, barycenter)
+                })
+                .collect();
+
+            // The sort is stable, so that ties keep their order.
+            layer.sort_by(|a, b| barycenters[a].total_cmp(&barycenters[b]));
+            update_positions(layer, &mut positions);
+        }
+    }
+}
+
+/// Places nodes of each layer side by side, and then moves them towards the barycenters of
+/// their neighbors while keeping their order and gaps.
+fn assign_x(vertices: &mut [Vertex], layers: &[Vec<usize>]) {
+    for layer in layers {
+        let mut x = 0.0;
+        for v in layer {
+            x += vertices[*v].width / 2.0;
+            vertices[*v].x = x;
+            x += vertices[*v].width / 2.0 + NODE_GAP;
+        }
+    }
+
+    for sweep in 0..SWEEPS {
+        let down = sweep % 2 == 0;
+        let order: Vec<usize> = if down {
+            (0..layers.len()).collect()
+        } else {
+            (0..layers.len()).rev().collect()
+        };
+
+        for index in order {
+            let layer = &layers[index];
+            let desired: Vec<f64> = layer
+                .iter()
+                .map(|v| {
+                    let vertex = &vertices[*v];
+                    let neighbors: Vec<usize> = if down {
+                        vertex.upper.clone()
+                    } else {
+                        vertex.lower.clone()
+                    };
+                    if neighbors.is_empty() {
+                        vertex.x
+                    } else {
+                        neighbors.iter().map(|u| vertices[*u].x).sum::<f64>()
+                            / neighbors.len() as f64
+                    }
+                })
+                .collect();
+
+            let gap = |i: usize| {
+                (vertices[layer[i - 1]].width + vertices[layer[i]].width) / 2.0 + NODE_GAP
+            };
+
+            // Both passes keep the order and gaps, and so does their average.
+            let mut left = desired.clone();
+            for i in 1..layer.len() {
+                left[i] = left[i].max(left[i - 1] + gap(i));
+            }
+            let mut right = desired;
+            for i in (1..layer.len()).rev() {
+                right[i - 1] = right[i - 1].min(right[i] - gap(i));
+            }
+
+            for (i, v) in layer.iter().enumerate() {
+                vertices[*v].x = (left[i] + right[i]) / 2.0;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sizes(n: usize) -> Vec<Size> {
+        vec![
+            Size {
+                width: 60.0,
+                height: 30.0,
+            };
+            n
+        ]
+    }
+
+    /// Nodes of a layout must not overlap, and must be inside the drawing.
+    fn assert_separated(layout: &Layout, sizes: &[Size]) {
+        for (a, (pa, sa)) in layout.nodes.iter().zip(sizes).enumerate() {
+            assert!(pa.x - sa.width / 2.0 >= 0.0 && pa.x + sa.width / 2.0 <= layout.width);
+            assert!(pa.y - sa.height / 2.0 >= 0.0 && pa.y + sa.height / 2.0 <= layout.height);
+            for (pb, sb) in layout.nodes.iter().zip(sizes).skip(a + 1) {
+                let apart_x = (pa.x - pb.x).abs() >= (sa.width + sb.width) / 2.0;
+                let apart_y = (pa.y - pb.y).abs() >= (sa.height + sb.height) / 2.0;
+                assert!(apart_x || apart_y, "{:?} overlaps {:?}", pa, pb);
+            }
+        }
+    }
+
+    #[test]
+    fn breaks_cycles_by_reversing_back_edges() {
+        let edges = [(0, 1), (1, 2), (2, 0)];
+        assert_eq!(find_back_edges(3, &edges), [false, false, true]);
+
+        let sizes = sizes(3);
+        let layout = layered_layout(&sizes, &edges);
+        assert_separated(&layout, &sizes);
+
+        // The cycle is drawn top-down, and the reversed edge goes back up.
+        let ys: Vec<f64> = layout.nodes.iter().map(|p| p.y).collect();
+        assert!(ys[0] < ys[1] && ys[1] < ys[2]);
+        for (points, &(s, t)) in layout.edges.iter().zip(&edges) {
+            let (first, last) = (points[0], points[points.len() - 1]);
+            assert_eq!(first.x, layout.nodes[s].x);
+            assert_eq!(last.x, layout.nodes[t].x);
+            assert_eq!(first.y > last.y, s == 2);
+        }
+    }
+
+    #[test]
+    fn routes_long_edges_through_dummy_nodes() {
+        // 3 is three layers below 0, so the edge from 0 to 3 passes through two dummy nodes.
+        let edges = [(0, 1), (1, 2), (2, 3), (0, 3)];
+        let sizes = sizes(4);
+        let layout = layered_layout(&sizes, &edges);
+        assert_separated(&layout, &sizes);
+
+        let layers = assign_layers(4, &edges.map(Some));
+        assert_eq!(layers, [0, 1, 2, 3]);
+
+        // One point per layer from 0 to 3, and none of the dummy nodes overlaps 1 or 2.
+        let points = &layout.edges[3];
+        assert_eq!(points.len(), 4);
+        for (point, node) in points[1..3].iter().zip([1, 2]) {
+            assert_eq!(point.y, layout.nodes[node].y);
+            assert!(
+                (point.x - layout.nodes[node].x).abs() >= (DUMMY_WIDTH + sizes[node].width) / 2.0
+            );
+        }
+        assert!(layout.edges[..3].iter().all(|points| points.len() == 2));
+    }
+
+    #[test]
+    fn leaves_self_loops_to_renderers() {
+        let edges = [(0, 0), (0, 1), (0, 1)];
+        let sizes = sizes(2);
+        let layout = layered_layout(&sizes, &edges);
+        assert_separated(&layout, &sizes);
+
+        assert!(layout.edges[0].is_empty());
+        assert_eq!(layout.edges[1], layout.edges[2]);
+        assert_eq!(find_back_edges(2, &edges), [false, false, false]);
+    }
+
+    #[test]
+    fn lays_out_empty_graphs() {
+        let layout = layered_layout(&[], &[]);
+        assert!(layout.nodes.is_empty() && layout.edges.is_empty());
+        assert!(layout.width >= 0.0 && layout.height >= 0.0);
+    }
+}
@@ -0,0 +1,89 @@
+use crate::conn::{self, Model};
+
+/// Renders a model as a Mermaid flowchart, which Markdown renderers draw natively. Components are
+/// labeled with their labels or identifier values, and connectors with their connector types.
+pub fn get_mermaid(model: &Model) -> String {
+    let mut s = String::from("flowchart LR\n");
+
+    for component in &model.components {
+        let label = conn::get_label_lines(component)
+            .iter()
+            .map(|line| escape(line))
+            .collect::<Vec<_>>()
+            .join("<br/>");
+        s.push_str(&format!("    {}[\"{}\"]\n", component.id, label));
+    }
+
+    for connector in &model.connectors {
+        let mut label = connector.connector_type.clone();
+        if connector.count > 0 {
+            label.push_str(&format!(" ({})", connector.count));
+        }
+        s.push_str(&format!(
+            "    {} -->|\"{}\"| {}\n",
+            connector.source_component_id,
+            escape(&label),
+            connector.target_component_id
+        ));
+    }
+
+    s
+}
+
+/// Escapes text in a quoted Mermaid label with entity codes. `#` starts an entity code, so it is
+/// escaped first.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '#' => escaped.push_str("#35;"),
+            '"' => escaped.push_str("#quot;"),
+            '<' => escaped.push_str("#lt;"),
+            '>' => escaped.push_str("#gt;"),
+            '&' => escaped.push_str("#amp;"),
+            '|' => escaped.push_str("#124;"),
+            '\n' => escaped.push_str("<br/>"),
+            _ => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_label_syntax() {
+        assert_eq!(
+            escape("#1 \"a\" <b> & c|d\ne"),
+            "#35;1 #quot;a#quot; #lt;b#gt; #amp; c#124;d<br/>e"
+        );
+    }
+
+    #[test]
+    fn draws_labels_and_connector_counts() {
+        let mut model: Model = serde_json::from_str(
+            r#"{
+                "components": [
+                    {"id": "c1", "component_values": {"host": "a\"b"}},
+                    {"id": "c2", "component_values": {"host": "c"}}
+                ],
+                "connectors": [
+                    {"connector_type": "HTTP|2", "source_component_id": "c1", "target_component_id": "c2", "count": 3}
+                ]
+            }"#,
+        )
+        .unwrap();
+        model.components[1].label = Some(String::from("<api>"));
+
+        assert_eq!(
+            get_mermaid(&model),
+            "flowchart LR\n    \
+             c1[\"host:a#quot;b\"]\n    \
+             c2[\"#lt;api#gt;\"]\n    \
+             c1 -->|\"HTTP#124;2 (3)\"| c2\n"
+        );
+    }
+}
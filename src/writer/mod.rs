@@ -0,0 +1,157 @@
+use std::collections::BTreeMap;
+
+use crate::conn::Model;
+
+pub mod gexf;
+pub mod graphml;
+pub mod html;
+pub mod layout;
+pub mod mermaid;
+pub mod plantuml;
+pub mod structurizr;
+pub mod svg;
+
+/// The narrowest type of an attribute that every value can be parsed as
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum AttributeType {
+    Boolean,
+    Long,
+    Double,
+    String,
+}
+
+/// Whether the integer part of a number has a zero followed by other digits
+fn has_leading_zero(value: &str) -> bool {
+    let digits = value.strip_prefix(['-', '+']).unwrap_or(value);
+    let mut chars = digits.chars();
+    chars.next() == Some('0') && chars.next().is_some_and(|c| c.is_ascii_digit())
+}
+
+impl AttributeType {
+    fn of(value: &str) -> Self {
+        if value.parse::<bool>().is_ok() {
+            AttributeType::Boolean
+        } else if has_leading_zero(value) {
+            // Consumers would drop leading zeros of numbers, e.g., of "007".
+            AttributeType::String
+        } else if value.parse::<i64>().is_ok() {
+            AttributeType::Long
+        } else if value.parse::<f64>().is_ok_and(|v| v.is_finite()) {
+            AttributeType::Double
+        } else {
+            AttributeType::String
+        }
+    }
+
+    /// Widens the type so that a value of the other type can be parsed as well.
+    fn widen(self, other: Self) -> Self {
+        match (self, other) {
+            (a, b) if a == b => a,
+            (AttributeType::Long, AttributeType::Double)
+            | (AttributeType::Double, AttributeType::Long) => AttributeType::Double,
+            _ => AttributeType::String,
+        }
+    }
+
+    /// The type name, which GraphML and GEXF share
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            AttributeType::Boolean => "boolean",
+            AttributeType::Long => "long",
+            AttributeType::Double => "double",
+            AttributeType::String => "string",
+        }
+    }
+}
+
+/// Infers the type of each identifier from its values in every component of a model.
+pub(crate) fn get_identifier_types(model: &Model) -> BTreeMap<&str, AttributeType> {
+    let mut types: BTreeMap<&str, AttributeType> = BTreeMap::new();
+    for component in &model.components {
+        for (identifier, value) in &component.component_values {
+            let value_type = AttributeType::of(value);
+            types
+                .entry(identifier)
+                .and_modify(|t| *t = t.widen(value_type))
+                .or_insert(value_type);
+        }
+    }
+
+    types
+}
+
+/// Escapes text in XML attribute values and character data.
+pub(crate) fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            // Characters that XML 1.0 does not allow at all
+            c if (c as u32) < 0x20 && !matches!(c, '\t' | '\n' | '\r') => {}
+            _ => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn infers_the_narrowest_attribute_type() {
+        for (value, expected) in [
+            ("true", AttributeType::Boolean),
+            ("42", AttributeType::Long),
+            ("-42", AttributeType::Long),
+            ("0", AttributeType::Long),
+            ("0.5", AttributeType::Double),
+            ("1e3", AttributeType::Double),
+            ("007", AttributeType::String),
+            ("-007", AttributeType::String),
+            ("00.5", AttributeType::String),
+            ("NaN", AttributeType::String),
+            ("inf", AttributeType::String),
+            ("host-1", AttributeType::String),
+        ] {
+            assert_eq!(AttributeType::of(value), expected, "{}", value);
+        }
+    }
+
+    #[test]
+    fn widens_types_of_identifiers() {
+        let model: Model = serde_json::from_str(
+            r#"{
+                "components": [
+                    {"id": "c1", "component_values": {"port": "80", "load": "1", "tls": "true", "zip": "123"}},
+                    {"id": "c2", "component_values": {"port": "8080", "load": "0.5", "tls": "1", "zip": "007"}}
+                ],
+                "connectors": []
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            get_identifier_types(&model),
+            BTreeMap::from([
+                ("load", AttributeType::Double),
+                ("port", AttributeType::Long),
+                ("tls", AttributeType::String),
+                ("zip", AttributeType::String),
+            ])
+        );
+    }
+
+    #[test]
+    fn escapes_xml_and_drops_forbidden_characters() {
+        assert_eq!(
+            escape_xml("<a href=\"x\">'&'</a>\u{1}\t\n"),
+            "&lt;a href=&quot;x&quot;&gt;&apos;&amp;&apos;&lt;/a&gt;\t\n"
+        );
+    }
+}
@@ -0,0 +1,94 @@
+use crate::conn::{self, Model};
+
+/// Renders a model as a PlantUML component diagram, which AsciiDoc renderers draw natively.
+/// Components are labeled with their labels or identifier values, and connectors with their
+/// connector types.
+pub fn get_plantuml(model: &Model) -> String {
+    let mut s = String::from("@startuml\n");
+
+    for component in &model.components {
+        let label = conn::get_label_lines(component)
+            .iter()
+            .map(|line| escape(line))
+            .collect::<Vec<_>>()
+            .join("\\n");
+        s.push_str(&format!("component \"{}\" as {}\n", label, component.id));
+    }
+
+    for connector in &model.connectors {
+        let mut label = connector.connector_type.clone();
+        if connector.count > 0 {
+            label.push_str(&format!(" ({})", connector.count));
+        }
+        s.push_str(&format!(
+            "{} --> {} : {}\n",
+            connector.source_component_id,
+            connector.target_component_id,
+            escape(&label)
+        ));
+    }
+
+    s.push_str("@enduml\n");
+    s
+}
+
+/// Escapes text in a PlantUML label. Quotes and ampersands become character references, a
+/// backslash would start an escape sequence such as `\n`, and creole markup characters, e.g., the
+/// `//` of URLs, which would start italics, are escaped with `~`.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("&#34;"),
+            '&' => escaped.push_str("&#38;"),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '~' | '*' | '/' | '_' | '-' | '<' | '>' | '=' | '^' | '[' | ']' => {
+                escaped.push('~');
+                escaped.push(c);
+            }
+            _ => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_quotes_backslashes_and_creole_markup() {
+        assert_eq!(
+            escape("\"a\" & b\\c\nhttp://x_y"),
+            "&#34;a&#34; &#38; b\\\\c\\nhttp:~/~/x~_y"
+        );
+    }
+
+    #[test]
+    fn draws_labels_and_connector_counts() {
+        let mut model: Model = serde_json::from_str(
+            r#"{
+                "components": [
+                    {"id": "c1", "component_values": {"host": "a", "port": "80"}},
+                    {"id": "c2", "component_values": {"host": "c"}}
+                ],
+                "connectors": [
+                    {"connector_type": "HTTP", "source_component_id": "c1", "target_component_id": "c2", "count": 3}
+                ]
+            }"#,
+        )
+        .unwrap();
+        model.components[1].label = Some(String::from("api-gateway"));
+
+        assert_eq!(
+            get_plantuml(&model),
+            "@startuml\n\
+             component \"host:a\\nport:80\" as c1\n\
+             component \"api~-gateway\" as c2\n\
+             c1 --> c2 : HTTP (3)\n\
+             @enduml\n"
+        );
+    }
+}
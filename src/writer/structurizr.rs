@@ -0,0 +1,158 @@
+use std::collections::HashSet;
+
+use crate::conn::{self, Component, Model};
+
+/// Renders a model as a Structurizr DSL workspace for C4 documentation. Components become
+/// containers of one software system, named from the values of `name_keys`, and connectors
+/// become relationships tagged with their connector types.
+pub fn get_structurizr(model: &Model, name_keys: &[String]) -> String {
+    let mut s = String::from("workspace {\n");
+    s.push_str("    model {\n");
+    s.push_str("        system = softwareSystem \"Execution view\" {\n");
+
+    let mut names: HashSet<String> = HashSet::new();
+    for component in &model.components {
+        // Container names must be unique within a software system.
+        let mut name = get_container_name(component, name_keys);
+        if names.contains(&name) {
+            name = format!("{} ({})", name, component.id);
+        }
+        names.insert(name.clone());
+
+        s.push_str(&format!(
+            "            {} = container \"{}\" \"{}\"\n",
+            component.id,
+            escape(&name),
+            escape(&conn::format_values(&component.component_values))
+        ));
+    }
+    s.push_str("        }\n\n");
+
+    for connector in &model.connectors {
+        let connector_type = escape(&connector.connector_type);
+        s.push_str(&format!(
+            "        {} -> {} \"{}\" \"{}\" \"{}\"\n",
+            connector.source_component_id,
+            connector.target_component_id,
+            connector_type,
+            connector_type,
+            connector_type
+        ));
+    }
+    s.push_str("    }\n\n");
+
+    s.push_str("    views {\n");
+    s.push_str("        container system \"ExecutionView\" {\n");
+    s.push_str("            include *\n");
+    s.push_str("            autoLayout lr\n");
+    s.push_str("        }\n");
+    s.push_str("    }\n");
+    s.push_str("}\n");
+
+    s
+}
+
+/// Names a component by its label, or by joining the values of the name keys that it has with
+/// ":". Components without any of them are named by all of their identifier values, or by their
+/// IDs.
+fn get_container_name(component: &Component, name_keys: &[String]) -> String {
+    if let Some(label) = &component.label {
+        return label.clone();
+    }
+
+    let values: Vec<&str> = name_keys
+        .iter()
+        .filter_map(|key| component.component_values.get(key))
+        .map(|value| value.as_str())
+        .collect();
+    if !values.is_empty() {
+        return values.join(":");
+    }
+
+    if component.component_values.is_empty() {
+        return component.id.clone();
+    }
+
+    conn::format_values(&component.component_values)
+}
+
+/// Escapes text in a quoted Structurizr DSL string. Line breaks would end the statement.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '"' => escaped.push_str("\\\""),
+            '\n' | '\r' => escaped.push(' '),
+            _ => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn model() -> Model {
+        serde_json::from_str(
+            r#"{
+                "components": [
+                    {"id": "c1", "component_values": {"host": "a", "service": "api"}},
+                    {"id": "c2", "component_values": {"host": "b", "service": "api"}},
+                    {"id": "c3", "component_values": {"db": "users\n\"main\""}},
+                    {"id": "c4", "component_values": {}}
+                ],
+                "connectors": [
+                    {"connector_type": "SQL", "source_component_id": "c1", "target_component_id": "c3"}
+                ]
+            }"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn escapes_quotes_backslashes_and_line_breaks() {
+        assert_eq!(escape("a\\b \"c\"\r\nd"), "a\\\\b \\\"c\\\"  d");
+    }
+
+    #[test]
+    fn names_containers_uniquely() {
+        let mut model = model();
+        model.components[3].label = Some(String::from("Cache"));
+
+        assert_eq!(
+            get_structurizr(&model, &[String::from("service")]),
+            "workspace {\n    \
+             model {\n        \
+             system = softwareSystem \"Execution view\" {\n            \
+             c1 = container \"api\" \"host:a, service:api\"\n            \
+             c2 = container \"api (c2)\" \"host:b, service:api\"\n            \
+             c3 = container \"db:users \\\"main\\\"\" \"db:users \\\"main\\\"\"\n            \
+             c4 = container \"Cache\" \"\"\n        \
+             }\n\n        \
+             c1 -> c3 \"SQL\" \"SQL\" \"SQL\"\n    \
+             }\n\n    \
+             views {\n        \
+             container system \"ExecutionView\" {\n            \
+             include *\n            \
+             autoLayout lr\n        \
+             }\n    \
+             }\n\
+             }\n"
+        );
+    }
+
+    #[test]
+    fn falls_back_to_identifier_values_and_ids() {
+        let model = model();
+        let names: Vec<String> = model
+            .components
+            .iter()
+            .map(|c| get_container_name(c, &[String::from("host"), String::from("service")]))
+            .collect();
+
+        assert_eq!(names, ["a:api", "b:api", "db:users\n\"main\"", "c4"]);
+    }
+}
@@ -0,0 +1,364 @@
+use std::collections::HashMap;
+
+use crate::conn::{self, Model};
+
+use super::{
+    escape_xml,
+    layout::{self, Layout, Point, Size},
+};
+
+const FONT_SIZE: f64 = 12.0;
+const LINE_HEIGHT: f64 = 16.0;
+/// An estimate of the average width of a character, since text cannot be measured without fonts
+const CHAR_WIDTH: f64 = 7.0;
+const PADDING: f64 = 10.0;
+/// How far self-loops reach out of the right side of their nodes
+const LOOP_WIDTH: f64 = 30.0;
+
+/// A model laid out with the built-in layered layout
+pub(crate) struct ModelLayout<'a> {
+    /// The label lines of each component
+    pub labels: Vec<Vec<String>>,
+
+    /// The size of each component
+    pub sizes: Vec<Size>,
+
+    /// Connectors between known components with the indexes of their components, in the order
+    /// of `layout.edges`
+    pub connectors: Vec<(&'a conn::Connector, (usize, usize))>,
+
+    pub layout: Layout,
+}
+
+/// Lays out the components of a model as boxes sized by their label lines. Connectors that refer
+/// to unknown components are left out.
+pub(crate) fn layout_model(model: &Model) -> ModelLayout<'_> {
+    let index_of: HashMap<&str, usize> = model
+        .components
+        .iter()
+        .enumerate()
+        .map(|(index, component)| (component.id.as_str(), index))
+        .collect();
+
+    let labels: Vec<Vec<String>> = model.components.iter().map(conn::get_label_lines).collect();
+    let sizes: Vec<Size> = labels
+        .iter()
+        .map(|lines| Size {
+            width: lines
+                .iter()
+                .map(|line| line.chars().count() as f64 * CHAR_WIDTH)
+                .fold(0.0, f64::max)
+                + 2.0 * PADDING,
+            height: lines.len().max(1) as f64 * LINE_HEIGHT + PADDING,
+        })
+        .collect();
+
+    let connectors: Vec<(&conn::Connector, (usize, usize))> = model
+        .connectors
+        .iter()
+        .filter_map(|connector| {
+            let source = index_of.get(connector.source_component_id.as_str())?;
+            let target = index_of.get(connector.target_component_id.as_str())?;
+            Some((connector, (*source, *target)))
+        })
+        .collect();
+    let edges: Vec<(usize, usize)> = connectors.iter().map(|(_, edge)| *edge).collect();
+
+    ModelLayout {
+        layout: layout::layered_layout(&sizes, &edges),
+        labels,
+        sizes,
+        connectors,
+    }
+}
+
+/// Renders a model as an SVG image with the built-in layered layout, which needs no external
+/// programs. Components are boxes labeled with their labels or identifier values, and connectors
+/// are arrows labeled with their connector types and counts, which are thicker for larger counts.
+pub fn get_svg(model: &Model) -> String {
+    let ModelLayout {
+        labels,
+        sizes,
+        connectors,
+        layout,
+    } = layout_model(model);
+    let width = layout.width + LOOP_WIDTH;
+    let height = layout.height;
+
+    let mut s = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{:.0}\" height=\"{:.0}\" viewBox=\"0 0 {:.0} {:.0}\" \
+         font-family=\"sans-serif\" font-size=\"{}\">\n",
+        width, height, width, height, FONT_SIZE
+    );
+    s.push_str(
+        "  <defs>\n    <marker id=\"arrow\" viewBox=\"0 0 10 10\" refX=\"10\" refY=\"5\" \
+         markerWidth=\"8\" markerHeight=\"8\" markerUnits=\"userSpaceOnUse\" orient=\"auto\">\n      \
+         <path d=\"M 0 0 L 10 5 L 0 10 z\" fill=\"black\"/>\n    </marker>\n  </defs>\n",
+    );
+    s.push_str("  <rect width=\"100%\" height=\"100%\" fill=\"white\"/>\n");
+
+    let max_count = model.connectors.iter().map(|c| c.count).max().unwrap_or(0);
+    // Connectors between the same components share a path, so their labels are stacked.
+    let mut labels_on_edge: HashMap<(usize, usize), usize> = HashMap::new();
+    for ((connector, edge), points) in connectors.iter().zip(&layout.edges) {
+        let (path, mut label_at) = if points.is_empty() {
+            get_loop_path(layout.nodes[edge.0], sizes[edge.0])
+        } else {
+            let middle = (points.len() - 1) / 2;
+            let (a, b) = (points[middle], points[middle + 1]);
+            (
+                get_edge_path(points),
+                Point {
+                    x: (a.x + b.x) / 2.0,
+                    y: (a.y + b.y) / 2.0,
+                },
+            )
+        };
+        let key = (edge.0.min(edge.1), edge.0.max(edge.1));
+        let stacked = labels_on_edge.entry(key).or_insert(0);
+        label_at.y += *stacked as f64 * LINE_HEIGHT;
+        *stacked += 1;
+        let mut label = connector.connector_type.clone();
+        if connector.count > 0 {
+            label.push_str(&format!(" ({})", connector.count));
+        }
+
+        s.push_str(&format!(
+            "  <g class=\"connector\">\n    <title>{} -&gt; {}</title>\n    \
+             <path d=\"{}\" fill=\"none\" stroke=\"black\" stroke-width=\"{:.2}\" marker-end=\"url(#arrow)\"/>\n    \
+             <text x=\"{:.1}\" y=\"{:.1}\" dx=\"4\">{}</text>\n  </g>\n",
+            escape_xml(&connector.source_component_id),
+            escape_xml(&connector.target_component_id),
+            path,
+            conn::get_edge_penwidth(connector.count, max_count),
+            label_at.x,
+            label_at.y,
+            escape_xml(&label)
+        ));
+    }
+
+    for ((component, lines), (center, size)) in model
+        .components
+        .iter()
+        .zip(&labels)
+        .zip(layout.nodes.iter().zip(&sizes))
+    {
+        let left = center.x - size.width / 2.0;
+        let top = center.y - size.height / 2.0;
+        s.push_str(&format!(
+            "  <g class=\"component\" id=\"{}\">\n    \
+             <rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\" rx=\"4\" fill=\"#f5f5f5\" stroke=\"black\"/>\n",
+            escape_xml(&component.id),
+            left,
+            top,
+            size.width,
+            size.height
+        ));
+        for (index, line) in lines.iter().enumerate() {
+            s.push_str(&format!(
+                "    <text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"middle\">{}</text>\n",
+                center.x,
+                top + PADDING / 2.0 + (index as f64 + 0.75) * LINE_HEIGHT,
+                escape_xml(line)
+            ));
+        }
+        s.push_str("  </g>\n");
+    }
+
+    s.push_str("</svg>\n");
+    s
+}
+
+/// Draws a path through the points with curves that leave and enter nodes vertically.
+fn get_edge_path(points: &[Point]) -> String {
+    let mut d = format!("M {:.1} {:.1}", points[0].x, points[0].y);
+    for pair in points.windows(2) {
+        let (a, b) = (pair[0], pair[1]);
+        let middle = (a.y + b.y) / 2.0;
+        d.push_str(&format!(
+            " C {:.1} {:.1} {:.1} {:.1} {:.1} {:.1}",
+            a.x, middle, b.x, middle, b.x, b.y
+        ));
+    }
+
+    d
+}
+
+/// Draws a self-loop on the right side of a node, and returns where its label goes.
+fn get_loop_path(center: Point, size: Size) -> (String, Point) {
+    let right = center.x + size.width / 2.0;
+    let top = center.y - size.height / 4.0;
+    let bottom = center.y + size.height / 4.0;
+    let d = format!(
+        "M {:.1} {:.1} C {:.1} {:.1} {:.1} {:.1} {:.1} {:.1}",
+        right,
+        top,
+        right + LOOP_WIDTH,
+        top - LOOP_WIDTH / 2.0,
+        right + LOOP_WIDTH,
+        bottom + LOOP_WIDTH / 2.0,
+        right,
+        bottom
+    );
+
+    (
+        d,
+        Point {
+            x: right + LOOP_WIDTH * 0.75,
+            y: center.y,
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn model(json: &str) -> Model {
+        serde_json::from_str(json).unwrap()
+    }
+
+    /// The positions of the text elements with the content
+    fn text_positions(svg: &str, content: &str) -> Vec<(f64, f64)> {
+        svg.lines()
+            .filter(|line| line.trim_start().starts_with("<text") && line.contains(content))
+            .map(|line| {
+                let attribute = |name: &str| -> f64 {
+                    let start = line.find(&format!(" {}=\"", name)).unwrap() + name.len() + 3;
+                    let end = start + line[start..].find('"').unwrap();
+                    line[start..end].parse().unwrap()
+                };
+                (attribute("x"), attribute("y"))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn escapes_labels_and_ids() {
+        let mut model = model(
+            r#"{
+                "components": [
+                    {"id": "a&b", "component_values": {"host": "<a>"}},
+                    {"id": "c", "component_values": {"host": "c"}}
+                ],
+                "connectors": [
+                    {"connector_type": "\"HTTP\"", "source_component_id": "a&b", "target_component_id": "c", "count": 1}
+                ]
+            }"#,
+        );
+        model.components[1].label = Some(String::from("it's <c>"));
+
+        let svg = get_svg(&model);
+        assert!(svg.contains("id=\"a&amp;b\""), "{}", svg);
+        assert!(svg.contains(">host:&lt;a&gt;</text>"), "{}", svg);
+        assert!(svg.contains(">it&apos;s &lt;c&gt;</text>"), "{}", svg);
+        assert!(svg.contains("<title>a&amp;b -&gt; c</title>"), "{}", svg);
+        assert!(svg.contains(">&quot;HTTP&quot; (1)</text>"), "{}", svg);
+        assert!(!svg.contains("<a>") && !svg.contains("<c>"), "{}", svg);
+    }
+
+    #[test]
+    fn draws_self_loops_beside_their_nodes() {
+        let model = model(
+            r#"{
+                "components": [{"id": "a", "component_values": {"host": "a"}}],
+                "connectors": [
+                    {"connector_type": "IPC", "source_component_id": "a", "target_component_id": "a", "count": 1}
+                ]
+            }"#,
+        );
+
+        let model_layout = layout_model(&model);
+        assert!(model_layout.layout.edges[0].is_empty());
+        let center = model_layout.layout.nodes[0];
+        let size = model_layout.sizes[0];
+        let (path, label_at) = get_loop_path(center, size);
+
+        let svg = get_svg(&model);
+        assert!(svg.contains(&format!("<path d=\"{}\"", path)), "{}", svg);
+        // The loop and its label are on the right side of the node, inside the drawing.
+        assert!(label_at.x > center.x + size.width / 2.0);
+        assert_eq!(
+            text_positions(&svg, ">IPC (1)<"),
+            [((label_at.x * 10.0).round() / 10.0, label_at.y)]
+        );
+        assert!(svg.contains(&format!(
+            "width=\"{:.0}\"",
+            model_layout.layout.width + LOOP_WIDTH
+        )));
+    }
+
+    #[test]
+    fn stacks_labels_of_parallel_connectors() {
+        let model = model(
+            r#"{
+                "components": [
+                    {"id": "a", "component_values": {"host": "a"}},
+                    {"id": "b", "component_values": {"host": "b"}}
+                ],
+                "connectors": [
+                    {"connector_type": "HTTP", "source_component_id": "a", "target_component_id": "b", "count": 1},
+                    {"connector_type": "gRPC", "source_component_id": "a", "target_component_id": "b", "count": 2},
+                    {"connector_type": "AMQP", "source_component_id": "b", "target_component_id": "a", "count": 1}
+                ]
+            }"#,
+        );
+
+        let svg = get_svg(&model);
+        let [(x, y)] = text_positions(&svg, ">HTTP (1)<")[..] else {
+            panic!("{}", svg);
+        };
+        // Connectors between the same components in either direction share a path.
+        assert_eq!(text_positions(&svg, ">gRPC (2)<"), [(x, y + LINE_HEIGHT)]);
+        assert_eq!(
+            text_positions(&svg, ">AMQP (1)<"),
+            [(x, y + 2.0 * LINE_HEIGHT)]
+        );
+    }
+
+    #[test]
+    fn drops_connectors_to_unknown_components() {
+        let model = model(
+            r#"{
+                "components": [
+                    {"id": "a", "component_values": {"host": "a"}},
+                    {"id": "b", "component_values": {"host": "b"}}
+                ],
+                "connectors": [
+                    {"connector_type": "HTTP", "source_component_id": "a", "target_component_id": "b", "count": 1},
+                    {"connector_type": "HTTP", "source_component_id": "a", "target_component_id": "x", "count": 1},
+                    {"connector_type": "HTTP", "source_component_id": "y", "target_component_id": "b", "count": 1}
+                ]
+            }"#,
+        );
+
+        let model_layout = layout_model(&model);
+        assert_eq!(model_layout.connectors.len(), 1);
+        assert_eq!(model_layout.connectors[0].1, (0, 1));
+        assert_eq!(model_layout.layout.edges.len(), 1);
+
+        let svg = get_svg(&model);
+        assert_eq!(svg.matches("<g class=\"connector\">").count(), 1);
+        assert_eq!(svg.matches("<g class=\"component\"").count(), 2);
+    }
+
+    #[test]
+    fn draws_empty_models() {
+        let model = Model::default();
+        let model_layout = layout_model(&model);
+        assert!(model_layout.layout.nodes.is_empty());
+
+        let svg = get_svg(&model);
+
+        assert!(svg.starts_with("<?xml"));
+        assert!(svg.ends_with("</svg>\n"));
+        assert!(!svg.contains("<g class="));
+        // Only the room for self-loops is left.
+        assert_eq!(
+            (model_layout.layout.width, model_layout.layout.height),
+            (0.0, 0.0)
+        );
+        assert!(svg.contains("width=\"30\" height=\"0\""), "{}", svg);
+    }
+}